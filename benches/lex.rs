@@ -0,0 +1,69 @@
+use std::time::Instant;
+
+use compiler::lex::Lexer;
+use compiler::Options;
+
+// 词法分析的线性时间基准：源程序规模翻倍，耗时也应只翻倍
+// 样例中混入中文标识符，覆盖多字节UTF-8字符
+const SAMPLE: &str = "begin
+  integer k;
+  integer 阶乘;
+  integer function F(n);
+    begin
+      integer n;
+      if n<=0
+      then F:=1
+      else F:=n*F(n-1);
+    end;
+  read(m);
+  k:=F(m);
+  阶乘:=k;
+  write(k);
+end
+";
+
+fn make_source(size: usize) -> String {
+    // 重复样例直到达到指定字节数
+    let mut s = String::with_capacity(size + SAMPLE.len());
+    while s.len() < size {
+        s.push_str(SAMPLE);
+    }
+    s
+}
+
+fn lex_all(source: String) -> usize {
+    // 词法分析整个源程序，返回记号个数；样例没有词法错误，出错说明词法分析有问题
+    let tokens = Lexer::new(source, &Options::default()).analyse();
+    assert!(tokens.diagnostics.is_empty(), "lexical errors: {:?}", tokens.diagnostics);
+    tokens.tokens.len()
+}
+
+fn main() {
+    let sizes = [128 * 1024, 256 * 1024, 512 * 1024, 1024 * 1024];
+    let mut per_byte = Vec::new();
+    // 每个样例的记号数，文件末尾另有一个Eof
+    let sample = lex_all(SAMPLE.to_string()) - 1;
+    println!("{:>10} {:>10} {:>10} {:>10}", "bytes", "tokens", "ms", "ns/byte");
+    for size in sizes {
+        let source = make_source(size);
+        let bytes = source.len();
+        let start = Instant::now();
+        let tokens = lex_all(source);
+        let elapsed = start.elapsed();
+        assert_eq!(tokens, bytes / SAMPLE.len() * sample + 1, "token count for {} bytes", bytes);
+        let ns = elapsed.as_nanos() as f64 / bytes as f64;
+        per_byte.push(ns);
+        println!(
+            "{:>10} {:>10} {:>10.2} {:>10.2}",
+            bytes,
+            tokens,
+            elapsed.as_secs_f64() * 1000.0,
+            ns
+        );
+    }
+    // 线性时间意味着每字节耗时大致不变
+    let ratio = per_byte[per_byte.len() - 1] / per_byte[0];
+    println!("ns/byte ratio (1MB / 128KB): {:.2}", ratio);
+    // 平方时间的话比值约为8，留出计时抖动的余量
+    assert!(ratio < 4.0, "lexing time is not linear in input size");
+}
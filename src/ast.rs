@@ -0,0 +1,401 @@
+use crate::env::Env;
+use crate::lex::Span;
+use crate::types::Type;
+
+// 抽象语法树，由语法分析器产生，交给语义检查使用
+// 各节点记录关键记号的位置，供报错使用
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct Ast {
+    // <程序> → [<引用说明>] <分程序>
+    // <单元> → unit <标识符>; [<引用说明>] interface <说明语句表> implementation <说明语句表> end
+    // 单元的实现部分作为没有执行语句的分程序
+    pub uses: Vec<(String, Span)>, // 引用的单元名
+    pub unit: Option<Unit>, // 单元的单元名和接口部分，程序为None
+    pub block: Block,
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct Unit {
+    pub name: String,
+    pub interface: Vec<Declaration>, // 接口部分，过程和函数只有首部，作为前置说明
+    pub span: Span,
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct Block {
+    // <分程序> → begin <说明语句表><执行语句表> end
+    pub declarations: Vec<Declaration>, // 说明语句表
+    pub statements: Vec<Statement>, // 执行语句表
+}
+
+impl Block {
+    pub fn parameter_type(&self, name: &str, env: &Env) -> TypeSpec {
+        // 形参的类型由函数体中同名的说明语句确定，没有说明时为integer
+        self.declarations
+            .iter()
+            .find_map(|d| match d {
+                Declaration::Variable { name: n, vtype, .. } if env.same(n, name) => Some(vtype.clone()),
+                _ => None,
+            })
+            .unwrap_or(TypeSpec::Integer)
+    }
+    pub fn loop_variables(&self) -> Vec<&str> {
+        // 执行语句表中for循环的控制变量名，不含嵌套过程中的循环
+        let mut names = Vec::new();
+        for s in &self.statements {
+            s.loop_variables(&mut names);
+        }
+        names
+    }
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub enum TypeSpec {
+    // 说明语句中写出的类型，上下界是常量表达式，类型名在语义检查时查符号表
+    Integer,
+    Boolean,
+    Named {
+        name: String,
+        span: Span,
+    },
+    Subrange {
+        low: Expression,
+        high: Expression,
+    },
+    Array {
+        low: Expression,
+        high: Expression,
+        element: Box<TypeSpec>,
+    },
+    Record {
+        fields: Vec<Field>,
+    },
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct Field {
+    // <域说明> → <类型> <变量表>，多个域拆成多个Field
+    pub name: String,
+    pub ftype: TypeSpec,
+    pub span: Span,
+}
+
+impl TypeSpec {
+    pub fn resolve(&self, env: &Env) -> Option<Type> {
+        // 求出上下界、查出类型名得到类型，无法确定时返回None
+        let constant = |n: &str| env.constant(n);
+        match self {
+            TypeSpec::Integer => Some(Type::Integer),
+            TypeSpec::Boolean => Some(Type::Boolean),
+            TypeSpec::Named { name, .. } => env.named_type(name),
+            TypeSpec::Subrange { low, high } => Some(Type::Subrange {
+                low: low.fold(&constant)?,
+                high: high.fold(&constant)?,
+            }),
+            TypeSpec::Array { low, high, element } => Some(Type::Array {
+                low: low.fold(&constant)?,
+                high: high.fold(&constant)?,
+                element: Box::new(element.resolve(env)?),
+            }),
+            TypeSpec::Record { fields } => Some(Type::Record {
+                fields: fields
+                    .iter()
+                    .map(|f| Some((f.name.clone(), f.ftype.resolve(env)?)))
+                    .collect::<Option<_>>()?,
+            }),
+        }
+    }
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub enum Declaration {
+    // <说明语句> → <类型> <变量>{,<变量>} | <类型> function <标识符>(<形参表>);<函数体>
+    //            | procedure <标识符>[(<形参表>)];<函数体> | const <标识符> = <条件表达式>
+    //            | type <标识符> = <类型> | 函数或过程的首部;forward
+    // 一个说明语句中的多个变量拆成多个Variable，过程是没有返回值的Function，多个常量、类型各自拆开
+    Variable {
+        name: String,
+        vtype: TypeSpec,
+        span: Span,
+    },
+    Constant {
+        name: String,
+        value: Expression, // 常量表达式，可以引用之前说明的常量
+        span: Span,
+    },
+    Type {
+        name: String,
+        spec: TypeSpec,
+        span: Span,
+    },
+    Function {
+        name: String,
+        return_type: Option<TypeSpec>, // 过程为None
+        parameters: Vec<Parameter>,
+        body: Block,
+        span: Span,
+    },
+    Forward {
+        // 前置说明只有首部，形参都是integer，之后同一作用域中要有首部相同的定义
+        name: String,
+        return_type: Option<TypeSpec>,
+        parameters: Vec<Parameter>,
+        span: Span,
+    },
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct Parameter {
+    // <形参> → [var] <变量>
+    pub name: String,
+    pub by_reference: bool, // 是否为var形参
+    pub span: Span,
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub enum Statement {
+    // <执行语句> → <读语句>│<写语句>│<赋值语句>│<调用语句>│<条件语句>│<当型循环语句>│<直到型循环语句>│<计数循环语句>│<情况语句>│<复合语句>│<空语句>
+    Read {
+        target: Expression, // 变量、数组元素或记录的域
+        span: Span,
+    },
+    Write {
+        arguments: Vec<Expression>, // 输出项，可以是字符串常量
+        newline: bool, // 是否为writeln，输出完换行
+        span: Span,
+    },
+    Assign {
+        target: Expression, // 变量、数组元素或记录的域
+        value: Expression,
+        span: Span,
+    },
+    Call {
+        name: String,
+        arguments: Vec<Expression>,
+        span: Span,
+    },
+    If {
+        condition: Expression,
+        then_branch: Box<Statement>,
+        else_branch: Option<Box<Statement>>, // else分支可以省略
+        span: Span,
+    },
+    While {
+        condition: Expression,
+        body: Box<Statement>,
+        span: Span,
+    },
+    Repeat {
+        body: Vec<Statement>, // 循环体是一个执行语句表
+        condition: Expression,
+        span: Span,
+    },
+    For {
+        variable: String, // 控制变量
+        variable_span: Span,
+        start: Expression, // 初值和终值只在进入循环时求值一次
+        end: Expression,
+        downward: bool, // 是否为downto
+        body: Box<Statement>,
+        span: Span,
+    },
+    Case {
+        selector: Expression, // 只求值一次
+        arms: Vec<CaseArm>,
+        otherwise: Option<Vec<Statement>>, // else分支是一个执行语句表，可以省略
+        span: Span,
+    },
+    Compound {
+        statements: Vec<Statement>,
+        span: Span,
+    },
+    Empty {
+        span: Span,
+    },
+}
+
+impl Statement {
+    fn loop_variables<'a>(&'a self, names: &mut Vec<&'a str>) {
+        // 收集语句中（包括嵌套的语句）for循环的控制变量名
+        match self {
+            Statement::If { then_branch, else_branch, .. } => {
+                then_branch.loop_variables(names);
+                if let Some(else_branch) = else_branch {
+                    else_branch.loop_variables(names);
+                }
+            }
+            Statement::While { body, .. } => body.loop_variables(names),
+            Statement::Repeat { body: statements, .. } | Statement::Compound { statements, .. } => {
+                for s in statements {
+                    s.loop_variables(names);
+                }
+            }
+            Statement::For { variable, body, .. } => {
+                names.push(variable);
+                body.loop_variables(names);
+            }
+            Statement::Case { arms, otherwise, .. } => {
+                for arm in arms {
+                    arm.body.loop_variables(names);
+                }
+                for s in otherwise.iter().flatten() {
+                    s.loop_variables(names);
+                }
+            }
+            _ => (),
+        }
+    }
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct CaseArm {
+    // <情况分支> → <情况标号>{,<情况标号>} : <执行语句>
+    pub labels: Vec<CaseLabel>,
+    pub body: Statement,
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct CaseLabel {
+    // <情况标号> → <常量表达式>[..<常量表达式>]
+    pub low: Expression,
+    pub high: Option<Expression>, // 单个值时为None
+}
+
+impl CaseLabel {
+    pub fn span(&self) -> Span {
+        // 报错位置取标号的下界
+        self.low.span()
+    }
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub enum Expression {
+    // 算术表达式与条件表达式
+    Variable {
+        name: String,
+        span: Span,
+    },
+    Literal {
+        value: i64,
+        span: Span,
+    },
+    Boolean {
+        value: bool,
+        span: Span,
+    },
+    String {
+        value: String,
+        span: Span,
+    },
+    Call {
+        name: String,
+        arguments: Vec<Expression>,
+        span: Span,
+    },
+    Index {
+        // a[i, j] 看作 a[i][j]
+        array: Box<Expression>,
+        index: Box<Expression>,
+        span: Span, // 左方括号的位置
+    },
+    Field {
+        record: Box<Expression>,
+        field: String,
+        span: Span, // 域名的位置
+    },
+    Unary {
+        operator: Operator, // 正号、负号和not
+        operand: Box<Expression>,
+        span: Span, // 运算符的位置
+    },
+    Binary {
+        operator: Operator,
+        left: Box<Expression>,
+        right: Box<Expression>,
+        span: Span, // 运算符的位置
+    },
+}
+
+impl Expression {
+    pub fn span(&self) -> Span {
+        match self {
+            Expression::Variable { span, .. } => *span,
+            Expression::Literal { span, .. } => *span,
+            Expression::Boolean { span, .. } => *span,
+            Expression::String { span, .. } => *span,
+            Expression::Call { span, .. } => *span,
+            Expression::Index { span, .. } => *span,
+            Expression::Field { span, .. } => *span,
+            Expression::Unary { span, .. } => *span,
+            Expression::Binary { span, .. } => *span,
+        }
+    }
+    pub fn fold(&self, constant: &dyn Fn(&str) -> Option<i64>) -> Option<i64> {
+        // 常量折叠：只由常数和命名常量组成的表达式在编译时求值，constant按名字查常量的值
+        // 含变量、函数调用，或者除数为0、溢出时返回None，留给运行时处理
+        match self {
+            Expression::Literal { value, .. } => Some(*value),
+            Expression::Boolean { value, .. } => Some(*value as i64),
+            Expression::Variable { name, .. } => constant(name),
+            Expression::Unary { operator, operand, .. } => {
+                let v = operand.fold(constant)?;
+                match operator {
+                    Operator::Minus => v.checked_neg(),
+                    Operator::Not => Some((v == 0) as i64),
+                    _ => Some(v),
+                }
+            }
+            Expression::Binary { operator, left, right, .. } => {
+                operator.apply(left.fold(constant)?, right.fold(constant)?)
+            }
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Operator {
+    // 算术运算符
+    Plus,
+    Minus,
+    Multiply,
+    Divide, // div，向零取整
+    Modulo, // mod，余数与被除数同号
+
+    // 逻辑运算符，布尔值用1和0表示
+    And,
+    Or,
+    Not,
+
+    // 关系运算符
+    Equal,
+    NotEqual,
+    Less,
+    LessEqual,
+    Greater,
+    GreaterEqual,
+}
+
+impl Operator {
+    pub fn apply(&self, a: i64, b: i64) -> Option<i64> {
+        // 对两个整数做二元运算，关系运算和逻辑运算的结果为1或0
+        // 除数为0或结果溢出时返回None
+        match self {
+            Operator::Plus => a.checked_add(b),
+            Operator::Minus => a.checked_sub(b),
+            Operator::Multiply => a.checked_mul(b),
+            Operator::Divide => a.checked_div(b),
+            Operator::Modulo => a.checked_rem(b),
+            Operator::Equal => Some((a == b) as i64),
+            Operator::NotEqual => Some((a != b) as i64),
+            Operator::Less => Some((a < b) as i64),
+            Operator::LessEqual => Some((a <= b) as i64),
+            Operator::Greater => Some((a > b) as i64),
+            Operator::GreaterEqual => Some((a >= b) as i64),
+            Operator::And => Some((a != 0 && b != 0) as i64),
+            Operator::Or => Some((a != 0 || b != 0) as i64),
+            Operator::Not => None,
+        }
+    }
+}
@@ -0,0 +1,736 @@
+use crate::ast::{Ast, Block, CaseLabel, Declaration, Statement, Expression, Operator, TypeSpec};
+use crate::env::{Env, ErrorMessage, Diagnostic, Diagnostics, Symbol};
+use crate::lex::{KEYWORDS, Span};
+use crate::options::Options;
+use crate::suggest::{suggest, with_suggestion};
+use crate::types::{Type, MAX_SIZE};
+use crate::unit::Interface;
+
+type Arguments = Vec<(Option<Type>, Span)>; // 各实参的类型和位置，类型未知时为None
+
+pub struct Checker {
+    // 语义检查，遍历抽象语法树，借助符号表栈检查声明、使用和类型
+    env: Env, // 符号表栈
+    procedure: String, // 当前所在过程名
+    functions: Vec<(String, i32)>, // 正在检查其函数体的函数名及其所在层次，由外向内
+    loop_variables: Vec<(String, i32)>, // 各层执行语句表中for循环的控制变量名及其所在层次，嵌套的过程不能给它们赋值
+    forward_calls: Vec<(String, i32, Arguments)>, // 定义之前对前置说明过程的调用：过程名、所在层次和实参
+    parameters: Vec<String>, // 当前函数中尚未在函数体里说明的形参
+    diagnostics: Diagnostics, // 收集到的报错信息
+    maxint: i64, // integer的最大值
+}
+
+impl Checker {
+    pub fn new(options: &Options) -> Self {
+        let mut env = Env::new();
+        env.trace = options.trace.env;
+        env.fold = options.language.case_insensitive();
+        env.predefine("maxint", Type::Integer, options.maxint());
+        Checker {
+            env,
+            procedure: "main".to_string(),
+            functions: Vec::new(),
+            loop_variables: Vec::new(),
+            forward_calls: Vec::new(),
+            parameters: Vec::new(),
+            diagnostics: Vec::new(),
+            maxint: options.maxint(),
+        }
+    }
+    pub fn analyse(mut self, ast: &Ast, units: &[Interface]) -> Diagnostics {
+        // <程序> → [<引用说明>] <分程序>
+        // 引用的单元导出的符号登记在最外层作用域中，units中还有间接引用的单元，只登记直接引用的
+        self.env.enter_scope();
+        for (name, span) in &ast.uses {
+            if let Some(unit) = units.iter().find(|u| &u.name == name)
+                && !unit.import(&mut self.env).is_empty()
+            {
+                self.error(ErrorMessage::FoundRepeatDeclarationInThisField, *span);
+            }
+        }
+        if let Some(unit) = &ast.unit {
+            // 单元中没有活动记录存放变量，只能说明常量、类型和过程
+            // 接口部分的过程首部作为前置说明，要在实现部分定义
+            for d in unit.interface.iter().chain(&ast.block.declarations) {
+                if let Declaration::Variable { span, .. } = d {
+                    self.error(ErrorMessage::VariableInUnit, *span);
+                }
+            }
+            for d in &unit.interface {
+                self.check_declaration(d);
+            }
+            self.check_block(&ast.block);
+            self.check_forwards(&unit.interface);
+        } else {
+            self.check_block(&ast.block);
+        }
+        self.env.exit_scope();
+        self.diagnostics
+    }
+
+    fn error(&mut self, errmsg: ErrorMessage, span: Span) {
+        self.diagnostics.push(Diagnostic { message: errmsg, span });
+    }
+    fn undeclared(&mut self, name: &str, span: Span) {
+        // 使用了未声明的符号，与可见的符号名或关键字拼写相近时附上建议
+        let visible = self.env.visible();
+        let suggestion = suggest(name, visible.into_iter().chain(KEYWORDS.iter().map(|(k, _)| *k))).map(str::to_string);
+        let message = with_suggestion(ErrorMessage::UndeclaredIdentifier(name.to_string()), suggestion.as_deref());
+        self.error(message, span);
+    }
+    fn check_block(&mut self, block: &Block) {
+        // 先登记说明语句表，再检查执行语句表
+        // 前置说明的过程要在同一个说明语句表中定义
+        // 执行语句表中的控制变量先记下，检查嵌套过程时用
+        let outer = self.loop_variables.len();
+        let level = self.env.level();
+        self.loop_variables.extend(block.loop_variables().into_iter().map(|n| (n.to_string(), level)));
+        for d in &block.declarations {
+            self.check_declaration(d);
+        }
+        self.check_forwards(&block.declarations);
+        for s in &block.statements {
+            self.check_statement(s);
+        }
+        self.loop_variables.truncate(outer);
+    }
+    fn check_declaration(&mut self, declaration: &Declaration) {
+        match declaration {
+            Declaration::Variable { name, vtype, span } => {
+                let vtype = self.check_type(vtype, *span);
+                // 函数体中对形参的说明，登记过形参后不算重复声明
+                // 形参的类型在外层作用域中求出（调用处和代码生成都按此处理），不能用函数体中说明的类型和常量
+                if let Some(i) = self.parameters.iter().position(|p| self.env.same(p, name)) {
+                    self.parameters.remove(i);
+                    // 报错后按函数体中说明的类型继续检查函数体，避免连带的报错
+                    if let Some(v) = self.env.find_variable_mut(name)
+                        && v.vtype != vtype
+                    {
+                        v.vtype = vtype;
+                        self.error(ErrorMessage::ParameterTypeInBody(name.clone()), *span);
+                    }
+                    return;
+                }
+                // 检查是否重复声明，若没有则添加声明
+                // 类型过大时已经报错，否则检查活动记录是否放得下
+                if self.env.check_repeat(name.clone()) {
+                    self.error(ErrorMessage::FoundRepeatDeclarationInThisField, *span);
+                } else {
+                    if !vtype.too_large() && !self.env.frame_fits(vtype.size()) {
+                        self.error(ErrorMessage::FrameTooLarge(self.procedure.clone()), *span);
+                    }
+                    self.env.add_variable(name.clone(), self.procedure.clone(), 0, vtype);
+                }
+            }
+            Declaration::Constant { name, value, span } => {
+                // 常量的值在编译时求出，只能引用之前说明的常量
+                let errors = self.diagnostics.len();
+                let ctype = self.check_expression(value);
+                let cvalue = value.fold(&|n| self.env.constant(n));
+                if ctype.is_some() && cvalue.is_none() && self.diagnostics.len() == errors {
+                    self.error(ErrorMessage::ExpectedConstant, value.span());
+                }
+                if self.env.check_repeat(name.clone()) {
+                    self.error(ErrorMessage::FoundRepeatDeclarationInThisField, *span);
+                } else {
+                    self.env.add_constant(name.clone(), ctype.unwrap_or(Type::Integer), cvalue.unwrap_or(0));
+                }
+            }
+            Declaration::Type { name, spec, span } => {
+                let ttype = self.check_type(spec, *span);
+                if self.env.check_repeat(name.clone()) {
+                    self.error(ErrorMessage::FoundRepeatDeclarationInThisField, *span);
+                } else {
+                    self.env.add_type(name.clone(), ttype);
+                }
+            }
+            Declaration::Function { name, return_type, parameters, body, span } => {
+                // 检查是否重复声明，若没有则添加声明，形参的类型和种类登记在过程表项中
+                // 形参类型的错误在函数体中的说明语句处报告
+                let ptype: Vec<Type> = parameters.iter().map(|p| self.resolve(&body.parameter_type(&p.name, &self.env))).collect();
+                let pkind: Vec<i32> = parameters.iter().map(|p| if p.by_reference { 2 } else { 1 }).collect();
+                let return_type = self.check_return_type(return_type, *span);
+                // 有前置说明时，定义的首部要与之一致，定义后不再是前置说明
+                // 前置说明中写不出形参类型，只比较形参个数、种类和返回值类型，形参类型以定义为准
+                let forward = self.env.forward(name).map(|p| (p.pkind.clone(), p.preturn.clone()));
+                match forward {
+                    Some(forward) => {
+                        if forward != (pkind.clone(), return_type.clone()) {
+                            self.error(ErrorMessage::ForwardMismatch(name.clone()), *span);
+                        }
+                        self.env.add_procedure(name.clone(), return_type, ptype.clone(), pkind.clone());
+                        // 定义之前的调用到这时才能检查实参类型
+                        let level = self.env.level();
+                        let (calls, rest) = std::mem::take(&mut self.forward_calls)
+                            .into_iter()
+                            .partition(|(n, l, _)| self.env.same(n, name) && *l == level);
+                        self.forward_calls = rest;
+                        for (_, _, arguments) in calls {
+                            self.check_argument_types(&ptype, &pkind, &arguments);
+                        }
+                    }
+                    None if self.env.check_repeat(name.clone()) => {
+                        self.error(ErrorMessage::FoundRepeatDeclarationInThisField, *span);
+                    }
+                    None => self.env.add_procedure(name.clone(), return_type, ptype.clone(), pkind),
+                }
+
+                // 函数体是新的作用域，形参登记在其中，形参的类型过大时已经报错
+                let oversized = ptype.iter().any(Type::too_large);
+                let outer_procedure = std::mem::replace(&mut self.procedure, name.clone());
+                let outer_parameters = std::mem::take(&mut self.parameters);
+                self.functions.push((name.clone(), self.env.level()));
+                self.env.enter_scope();
+                for (p, vtype) in parameters.iter().zip(ptype) {
+                    if self.env.check_repeat(p.name.clone()) {
+                        self.error(ErrorMessage::FoundRepeatDeclarationInThisField, p.span);
+                    } else {
+                        let vkind = if p.by_reference { 2 } else { 1 };
+                        self.env.add_variable(p.name.clone(), name.clone(), vkind, vtype);
+                        self.parameters.push(p.name.clone());
+                    }
+                }
+                let n = self.env.place_parameters(&self.parameters);
+                if n > MAX_SIZE && !oversized {
+                    self.error(ErrorMessage::FrameTooLarge(name.clone()), *span);
+                }
+                self.check_block(body);
+                self.env.exit_scope();
+                self.functions.pop();
+                self.parameters = outer_parameters;
+                self.procedure = outer_procedure;
+            }
+            Declaration::Forward { name, return_type, parameters, span } => {
+                // 前置说明没有函数体，定义之前就可以调用，形参类型到定义时才知道，先按integer占位
+                let ptype = vec![Type::Integer; parameters.len()];
+                let pkind = parameters.iter().map(|p| if p.by_reference { 2 } else { 1 }).collect();
+                let return_type = self.check_return_type(return_type, *span);
+                if self.env.check_repeat(name.clone()) {
+                    self.error(ErrorMessage::FoundRepeatDeclarationInThisField, *span);
+                } else {
+                    self.env.add_procedure(name.clone(), return_type, ptype, pkind);
+                    if let Some(p) = self.env.find_procedure_mut(name) {
+                        p.pstate = 1;
+                    }
+                }
+            }
+        }
+    }
+    fn check_forwards(&mut self, declarations: &[Declaration]) {
+        // 说明语句表结束时，前置说明的过程都要已经定义
+        for d in declarations {
+            if let Declaration::Forward { name, span, .. } = d
+                && let Some(p) = self.env.find_procedure_mut(name)
+                && p.pstate == 1
+            {
+                p.pstate = 0;
+                self.error(ErrorMessage::UndefinedForward(name.clone()), *span);
+            }
+        }
+    }
+    fn check_return_type(&mut self, return_type: &Option<TypeSpec>, span: Span) -> Option<Type> {
+        // 函数结果放在活动记录的一个单元中，只能是简单类型
+        let return_type = return_type.as_ref().map(|t| self.check_type(t, span));
+        if let Some(t) = &return_type
+            && !t.is_simple()
+        {
+            self.error(ErrorMessage::InvalidReturnType(t.clone()), span);
+        }
+        return_type
+    }
+    fn check_statement(&mut self, statement: &Statement) {
+        match statement {
+            Statement::Read { target, span } => {
+                // 只能读入整数
+                self.check_target(target);
+                let vtype = self.check_expression(target);
+                self.expect(Type::Integer, vtype, *span);
+            }
+            Statement::Write { arguments, .. } => {
+                // 只能输出字符串常量和简单类型的值
+                for a in arguments {
+                    if let Some(vtype) = self.check_expression(a)
+                        && !vtype.is_simple()
+                        && vtype != Type::String
+                    {
+                        self.error(ErrorMessage::TypeMismatch(Type::Integer, vtype), a.span());
+                    }
+                }
+            }
+            Statement::Assign { target, value, .. } => {
+                // 类型相容即可赋值，数组和记录整体赋值时复制全部单元
+                // 赋给子界的常量在编译时检查是否越界
+                self.check_target(target);
+                let ttype = self.check_expression(target);
+                let vtype = self.check_expression(value);
+                if let Some(Type::Subrange { low, high }) = ttype
+                    && let Some(v) = value.fold(&|n| self.env.constant(n))
+                    && (v < low || v > high)
+                {
+                    self.error(ErrorMessage::ValueOutOfRange(v, low, high), value.span());
+                }
+                if let Some(ttype) = ttype {
+                    self.expect(ttype, vtype, value.span());
+                }
+            }
+            Statement::Call { name, arguments, span } => {
+                // 调用语句只能调用过程
+                match self.env.lookup(name) {
+                    Some(Symbol::Procedure(p)) if p.preturn.is_none() => {
+                        self.check_arguments(name, arguments, *span);
+                    }
+                    Some(_) => {
+                        self.error(ErrorMessage::NotAProcedure(name.clone()), *span);
+                        self.check_argument_expressions(arguments);
+                    }
+                    None => {
+                        self.undeclared(name, *span);
+                        self.check_argument_expressions(arguments);
+                    }
+                }
+            }
+            Statement::If { condition, then_branch, else_branch, .. } => {
+                self.check_condition(condition);
+                self.check_statement(then_branch);
+                if let Some(else_branch) = else_branch {
+                    self.check_statement(else_branch);
+                }
+            }
+            Statement::While { condition, body, .. } => {
+                self.check_condition(condition);
+                self.check_statement(body);
+            }
+            Statement::Repeat { body, condition, .. } => {
+                for s in body {
+                    self.check_statement(s);
+                }
+                self.check_condition(condition);
+            }
+            Statement::For { variable, variable_span, start, end, body, .. } => {
+                // 控制变量必须是本过程中说明的integer或子界变量，不能是形参或外层的变量，初值和终值与之相容
+                // 循环体内控制变量只读，同一个变量不能作为嵌套循环的控制变量
+                let level = self.env.level();
+                let control = match self.env.lookup(variable) {
+                    Some(Symbol::Variable(v)) if Type::Integer.accepts(&v.vtype) && v.vkind == 0 && v.vlev == level => {
+                        Some((v.vtype.clone(), v.vreadonly))
+                    }
+                    Some(_) => {
+                        self.error(ErrorMessage::InvalidLoopVariable(variable.clone()), *variable_span);
+                        None
+                    }
+                    None => {
+                        self.undeclared(variable, *variable_span);
+                        None
+                    }
+                };
+                let stype = self.check_expression(start);
+                let etype = self.check_expression(end);
+                match control {
+                    Some((vtype, read_only)) => {
+                        if read_only {
+                            self.error(ErrorMessage::AssignToLoopVariable(variable.clone()), *variable_span);
+                        }
+                        self.expect(vtype.clone(), stype, start.span());
+                        self.expect(vtype, etype, end.span());
+                        self.env.set_read_only(variable, true);
+                        self.check_statement(body);
+                        self.env.set_read_only(variable, read_only);
+                    }
+                    None => self.check_statement(body),
+                }
+            }
+            Statement::Case { selector, arms, otherwise, .. } => {
+                // 选择表达式必须是integer、boolean或子界，标号是与之相容的常量
+                // 各标号（包括范围）之间不能重叠，重叠时在后出现的标号处报错
+                let stype = self.check_expression(selector);
+                let stype = match stype {
+                    Some(t) if t.is_simple() => Some(t),
+                    Some(t) => {
+                        self.error(ErrorMessage::TypeMismatch(Type::Integer, t), selector.span());
+                        None
+                    }
+                    None => None,
+                };
+                let mut seen: Vec<(i64, i64)> = Vec::new();
+                for arm in arms {
+                    for label in &arm.labels {
+                        if let Some((low, high)) = self.check_case_label(label, stype.clone()) {
+                            if let Some(&(l, _)) = seen.iter().find(|&&(l, h)| low <= h && l <= high) {
+                                self.error(ErrorMessage::DuplicateCaseLabel(low.max(l)), label.span());
+                            }
+                            seen.push((low, high));
+                        }
+                    }
+                    self.check_statement(&arm.body);
+                }
+                if let Some(otherwise) = otherwise {
+                    for s in otherwise {
+                        self.check_statement(s);
+                    }
+                }
+            }
+            Statement::Compound { statements, .. } => {
+                for s in statements {
+                    self.check_statement(s);
+                }
+            }
+            Statement::Empty { .. } => (),
+        }
+    }
+    fn check_case_label(&mut self, label: &CaseLabel, stype: Option<Type>) -> Option<(i64, i64)> {
+        // 返回标号覆盖的范围，不是常量或类型不符时返回None，不再检查重叠
+        let mut bounds = Vec::new();
+        for bound in std::iter::once(&label.low).chain(&label.high) {
+            let btype = self.check_expression(bound);
+            let matched = match (&stype, &btype) {
+                (Some(s), Some(b)) => s.accepts(b),
+                _ => false,
+            };
+            if let Some(stype) = stype.clone() {
+                self.expect(stype, btype.clone(), bound.span());
+            }
+            match bound.fold(&|n| self.env.constant(n)) {
+                Some(v) if matched => bounds.push(v),
+                Some(_) => (),
+                None if btype.is_some() => self.error(ErrorMessage::ExpectedConstant, bound.span()),
+                None => (),
+            }
+        }
+        let (low, high) = match (bounds.as_slice(), &label.high) {
+            (&[v], None) => (v, v),
+            (&[low, high], Some(_)) => (low, high),
+            _ => return None,
+        };
+        if low > high {
+            self.error(ErrorMessage::InvalidBounds(low, high), label.span());
+            return None;
+        }
+        Some((low, high))
+    }
+    fn check_condition(&mut self, condition: &Expression) {
+        // 条件必须是布尔类型
+        let vtype = self.check_expression(condition);
+        self.expect(Type::Boolean, vtype, condition.span());
+    }
+    fn check_expression(&mut self, expression: &Expression) -> Option<Type> {
+        // 返回表达式的类型，无法确定时返回None，此时错误已经报告过
+        match expression {
+            Expression::Variable { name, span } => self.check_symbol(name, *span),
+            Expression::Literal { .. } => Some(Type::Integer),
+            Expression::Boolean { .. } => Some(Type::Boolean),
+            Expression::String { .. } => Some(Type::String),
+            Expression::Call { name, arguments, span } => {
+                // 表达式中只能调用函数
+                match self.env.lookup(name) {
+                    Some(Symbol::Procedure(p)) if p.preturn.is_some() => {
+                        let preturn = p.preturn.clone();
+                        self.check_arguments(name, arguments, *span);
+                        preturn
+                    }
+                    Some(_) => {
+                        self.error(ErrorMessage::NotAFunction(name.clone()), *span);
+                        self.check_argument_expressions(arguments);
+                        None
+                    }
+                    None => {
+                        self.undeclared(name, *span);
+                        self.check_argument_expressions(arguments);
+                        None
+                    }
+                }
+            }
+            Expression::Index { array, index, span } => {
+                // 只能对数组使用下标，下标必须是整数，常量下标在编译时检查是否越界
+                let atype = self.check_expression(array);
+                let itype = self.check_expression(index);
+                self.expect(Type::Integer, itype, index.span());
+                match atype {
+                    Some(Type::Array { low, high, element }) => {
+                        if let Some(i) = index.fold(&|n| self.env.constant(n))
+                            && (i < low || i > high)
+                        {
+                            self.error(ErrorMessage::IndexOutOfBounds(i, low, high), *span);
+                        }
+                        Some(*element)
+                    }
+                    Some(_) => {
+                        self.error(ErrorMessage::NotAnArray, *span);
+                        None
+                    }
+                    None => None,
+                }
+            }
+            Expression::Field { record, field, span } => {
+                // 只能对记录选择域
+                match self.check_expression(record) {
+                    Some(rtype @ Type::Record { .. }) => match rtype.field(field, &self.env) {
+                        Some((_, ftype)) => Some(ftype.clone()),
+                        None => {
+                            self.error(ErrorMessage::NoSuchField(field.clone()), *span);
+                            None
+                        }
+                    },
+                    Some(_) => {
+                        self.error(ErrorMessage::NotARecord, *span);
+                        None
+                    }
+                    None => None,
+                }
+            }
+            Expression::Unary { operator, operand, .. } => {
+                let vtype = self.check_expression(operand);
+                let expected = match operator {
+                    Operator::Not => Type::Boolean,
+                    _ => Type::Integer,
+                };
+                self.expect(expected.clone(), vtype, operand.span());
+                self.check_overflow(expression, &[operand]);
+                Some(expected)
+            }
+            Expression::Binary { operator, left, right, span } => {
+                let ltype = self.check_expression(left);
+                let rtype = self.check_expression(right);
+                // 除数是值为0的常量表达式，编译时即可报错
+                if matches!(operator, Operator::Divide | Operator::Modulo) && right.fold(&|n| self.env.constant(n)) == Some(0) {
+                    self.error(ErrorMessage::DivisionByZero, *span);
+                } else {
+                    self.check_overflow(expression, &[left, right]);
+                }
+                match operator {
+                    Operator::Equal | Operator::NotEqual => {
+                        // 相等比较的两边是类型相同的简单类型
+                        if let Some(ltype) = ltype {
+                            if !ltype.is_simple() {
+                                self.error(ErrorMessage::TypeMismatch(Type::Integer, ltype.clone()), left.span());
+                            }
+                            self.expect(ltype, rtype, right.span());
+                        }
+                        Some(Type::Boolean)
+                    }
+                    Operator::Less | Operator::LessEqual | Operator::Greater | Operator::GreaterEqual => {
+                        self.expect(Type::Integer, ltype, left.span());
+                        self.expect(Type::Integer, rtype, right.span());
+                        Some(Type::Boolean)
+                    }
+                    Operator::And | Operator::Or | Operator::Not => {
+                        self.expect(Type::Boolean, ltype, left.span());
+                        self.expect(Type::Boolean, rtype, right.span());
+                        Some(Type::Boolean)
+                    }
+                    _ => {
+                        self.expect(Type::Integer, ltype, left.span());
+                        self.expect(Type::Integer, rtype, right.span());
+                        Some(Type::Integer)
+                    }
+                }
+            }
+        }
+    }
+    fn check_overflow(&mut self, expression: &Expression, operands: &[&Expression]) {
+        // 操作数都是范围内的常量，而运算结果超出integer的范围时报错，越界的操作数已经报告过
+        let fold = |e: &Expression| e.fold(&|n| self.env.constant(n));
+        let fits = |v: i64| (-self.maxint - 1..=self.maxint).contains(&v);
+        if operands.iter().all(|o| fold(o).is_some_and(fits)) && !fold(expression).is_some_and(fits) {
+            self.error(ErrorMessage::ConstantOverflow(self.maxint), expression.span());
+        }
+    }
+    fn check_argument_expressions(&mut self, arguments: &[Expression]) {
+        // 被调用的名字不是可调用的过程时，实参照样检查其中的符号
+        for a in arguments {
+            self.check_expression(a);
+        }
+    }
+    fn check_arguments(&mut self, name: &str, arguments: &[Expression], span: Span) {
+        // 实参个数和类型必须与形参一致，变量形参对应的实参必须是变量
+        // 前置说明的过程在定义之前被调用时，实参类型留到定义时检查
+        let (ptype, pkind, plev, pstate) = match self.env.lookup(name) {
+            Some(Symbol::Procedure(p)) => (p.ptype.clone(), p.pkind.clone(), p.plev, p.pstate),
+            _ => return,
+        };
+        let types: Vec<Option<Type>> = arguments.iter().map(|a| self.check_expression(a)).collect();
+        if pkind.len() != arguments.len() {
+            self.error(ErrorMessage::ArgumentCountMismatch(pkind.len(), arguments.len()), span);
+            return;
+        }
+        let mut checked: Arguments = Vec::new();
+        for (i, a) in arguments.iter().enumerate() {
+            if pkind[i] == 2 && !self.is_variable(a) {
+                self.error(ErrorMessage::ExpectedVariableArgument, a.span());
+                checked.push((None, a.span()));
+                continue;
+            }
+            if pkind[i] == 2 {
+                self.check_target(a);
+            }
+            checked.push((types[i].clone(), a.span()));
+        }
+        match pstate {
+            1 => self.forward_calls.push((name.to_string(), plev, checked)),
+            _ => self.check_argument_types(&ptype, &pkind, &checked),
+        }
+    }
+    fn check_argument_types(&mut self, ptype: &[Type], pkind: &[i32], arguments: &[(Option<Type>, Span)]) {
+        // 变量形参直接修改实参，类型必须完全相同，子界与integer也不相容
+        for ((t, span), (p, kind)) in arguments.iter().zip(ptype.iter().zip(pkind)) {
+            match t {
+                Some(t) if *kind == 2 && t != p => self.error(ErrorMessage::TypeMismatch(p.clone(), t.clone()), *span),
+                _ => self.expect(p.clone(), t.clone(), *span),
+            }
+        }
+    }
+    fn is_variable(&self, expression: &Expression) -> bool {
+        // 表达式是否是一个变量或变量的元素，可以取地址
+        match expression {
+            Expression::Variable { name, .. } => matches!(self.env.lookup(name), Some(Symbol::Variable(_))),
+            Expression::Index { array, .. } => self.is_variable(array),
+            Expression::Field { record, .. } => self.is_variable(record),
+            _ => false,
+        }
+    }
+    fn check_target(&mut self, target: &Expression) {
+        // 常量和循环体内的控制变量不能作为赋值、读语句的目标和变量形参的实参
+        // 外层用作控制变量的变量，在嵌套的过程中也不能赋值
+        if let Expression::Variable { name, span } = target {
+            match self.env.lookup(name) {
+                Some(Symbol::Constant(_)) => self.error(ErrorMessage::AssignToConstant(name.clone()), *span),
+                Some(Symbol::Variable(v)) if v.vreadonly => {
+                    self.error(ErrorMessage::AssignToLoopVariable(name.clone()), *span)
+                }
+                Some(Symbol::Variable(v))
+                    if v.vkind == 0
+                        && v.vlev < self.env.level()
+                        && self.loop_variables.iter().any(|(n, l)| *l == v.vlev && self.env.same(n, name)) =>
+                {
+                    self.error(ErrorMessage::AssignToLoopVariable(name.clone()), *span)
+                }
+                _ => (),
+            }
+        }
+    }
+    fn check_type(&mut self, vtype: &TypeSpec, span: Span) -> Type {
+        // 求出说明语句中写出的类型，类型名必须已经说明
+        // 无法确定的部分已经报错，按integer继续检查
+        match vtype {
+            TypeSpec::Integer => Type::Integer,
+            TypeSpec::Boolean => Type::Boolean,
+            TypeSpec::Named { name, span } => match self.env.lookup(name) {
+                Some(Symbol::Type(t)) => t.ttype.clone(),
+                Some(_) => {
+                    self.error(ErrorMessage::NotAType(name.clone()), *span);
+                    Type::Integer
+                }
+                None => {
+                    self.undeclared(name, *span);
+                    Type::Integer
+                }
+            },
+            TypeSpec::Subrange { low, high } => match self.check_bounds(low, high, span) {
+                Some((low, high)) => Type::Subrange { low, high },
+                None => Type::Integer,
+            },
+            TypeSpec::Array { low, high, element } => {
+                // 元素类型过大时已经报错，不再重复报告
+                let element = self.check_type(element, span);
+                match self.check_bounds(low, high, span) {
+                    Some((low, high)) => {
+                        let oversized = element.too_large();
+                        let atype = Type::Array { low, high, element: Box::new(element) };
+                        if !oversized && atype.too_large() {
+                            self.error(ErrorMessage::TypeTooLarge, span);
+                        }
+                        atype
+                    }
+                    None => Type::Integer,
+                }
+            }
+            TypeSpec::Record { fields } => {
+                // 同一记录中的域名不能重复，域的偏移按说明的顺序依次分配
+                let mut resolved: Vec<(String, Type)> = Vec::new();
+                for f in fields {
+                    let ftype = self.check_type(&f.ftype, f.span);
+                    if resolved.iter().any(|(n, _)| self.env.same(n, &f.name)) {
+                        self.error(ErrorMessage::FoundRepeatDeclarationInThisField, f.span);
+                        continue;
+                    }
+                    resolved.push((f.name.clone(), ftype));
+                }
+                let oversized = resolved.iter().any(|(_, t)| t.too_large());
+                let rtype = Type::Record { fields: resolved };
+                if !oversized && rtype.too_large() {
+                    self.error(ErrorMessage::TypeTooLarge, span);
+                }
+                rtype
+            }
+        }
+    }
+    fn check_bounds(&mut self, low: &Expression, high: &Expression, span: Span) -> Option<(i64, i64)> {
+        // 数组和子界的上下界必须是integer常量表达式，且下界不能大于上界
+        let mut bounds = Vec::new();
+        for bound in [low, high] {
+            let btype = self.check_expression(bound);
+            self.expect(Type::Integer, btype.clone(), bound.span());
+            match bound.fold(&|n| self.env.constant(n)) {
+                Some(v) => bounds.push(v),
+                None if btype.is_some() => self.error(ErrorMessage::ExpectedConstant, bound.span()),
+                None => (),
+            }
+        }
+        let [low, high] = bounds[..] else {
+            return None;
+        };
+        if low > high {
+            self.error(ErrorMessage::InvalidBounds(low, high), span);
+        }
+        Some((low, high))
+    }
+    fn resolve(&self, vtype: &TypeSpec) -> Type {
+        // 不报错地求出类型，无法确定时按integer处理
+        vtype.resolve(&self.env).unwrap_or(Type::Integer)
+    }
+    fn expect(&mut self, expected: Type, found: Option<Type>, span: Span) {
+        // 类型未知时不再重复报错
+        if let Some(found) = found
+            && !expected.accepts(&found)
+        {
+            self.error(ErrorMessage::TypeMismatch(expected, found), span);
+        }
+    }
+    fn check_symbol(&mut self, name: &str, span: Span) -> Option<Type> {
+        // 使用的符号必须在当前或外层作用域声明过
+        // 函数名作为变量使用时取函数的返回值类型，过程名不能作为变量使用
+        // 变量和函数结果都按（层差，偏移）访问，结果单元只在函数体及其嵌套的函数中可见
+        // 同层的其他函数中层差同样合法，但访问到的是它自己的结果单元，所以按外层函数判断
+        if let Some(Symbol::Procedure(p)) = self.env.lookup(name)
+            && p.preturn.is_some()
+            && !self.functions.iter().any(|(f, level)| *level == p.plev && self.env.same(f, name))
+        {
+            self.error(ErrorMessage::ResultOutsideFunction(name.to_string()), span);
+            return None;
+        }
+        if let Some((level, offset, _)) = self.env.address(name)
+            && level > 0
+            && self.env.trace
+        {
+            eprintln!("[env] non-local {} (level difference {}, offset {})", name, level, offset);
+        }
+        match self.env.lookup(name) {
+            Some(Symbol::Variable(v)) => Some(v.vtype.clone()),
+            Some(Symbol::Constant(c)) => Some(c.ctype.clone()),
+            Some(Symbol::Type(_)) => {
+                self.error(ErrorMessage::NotAVariable(name.to_string()), span);
+                None
+            }
+            Some(Symbol::Procedure(p)) => {
+                let preturn = p.preturn.clone();
+                if preturn.is_none() {
+                    self.error(ErrorMessage::NotAFunction(name.to_string()), span);
+                }
+                preturn
+            }
+            None => {
+                self.undeclared(name, span);
+                None
+            }
+        }
+    }
+}
@@ -0,0 +1,703 @@
+use crate::ast::{Ast, Block, CaseArm, Declaration, Statement, Expression, Operator, TypeSpec};
+use crate::env::{Env, Symbol};
+use crate::lex::Span;
+use crate::options::Options;
+use crate::types::Type;
+use crate::unit::Interface;
+use std::collections::HashMap;
+use std::fmt;
+
+// 活动记录的布局，相对于基址bp：
+// bp-n .. bp-1 实参，由调用者压栈
+// bp+0 静态链，bp+1 动态链，bp+2 返回地址
+// bp+3 函数结果，bp+4 起为局部变量
+pub const RESULT: i32 = 3; // 函数结果的偏移
+pub const FIRST_LOCAL: i32 = 4; // 第一个局部变量的偏移
+const MAX_TABLE: i64 = 1024; // 情况语句跳转表的最大长度
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Instruction {
+    // 栈式虚拟机的指令
+    Lit(i64), // 常数入栈
+    Lod(i32, i32), // 层差、偏移：变量的值入栈
+    Sto(i32, i32), // 层差、偏移：栈顶存入变量
+    Lda(i32, i32), // 层差、偏移：变量的地址入栈
+    Ldi(i32), // 单元数：弹出地址，从该地址起的若干单元的值依次入栈
+    Sti(i32), // 单元数：弹出地址和若干个值，依次存入从该地址起的单元
+    Idx(i64, i64), // 下界、元素大小：弹出下标和数组地址，元素地址入栈
+    Chk(i64, i64), // 下界、上界：检查栈顶的下标是否越界
+    Rng(i64, i64), // 下界、上界：检查栈顶将要存入子界变量的值是否越界
+    Opr(Operator), // 弹出两个操作数，结果入栈
+    Neg, // 栈顶取负
+    Not, // 栈顶取逻辑非
+    Jmp(usize), // 无条件跳转
+    Jpc(usize), // 栈顶为0时跳转
+    Jtb(i64, i64, usize), // 下界、上界、缺省目标：弹出栈顶v，越界时跳到缺省目标，否则跳过其后的v-下界条跳转指令
+    Cal(i32, usize), // 层差、入口：调用过程
+    Ent(i32), // 开辟函数结果和局部变量的空间
+    Ret(i32, bool), // 实参单元数、是否为函数：弹出实参后返回，函数结果入栈
+    Red, // 读入一个整数入栈
+    Wrt(bool), // 是否为布尔值：弹出栈顶并输出
+    Wrs(usize), // 常量池下标：输出字符串常量
+    Wln, // 输出换行
+    Hlt, // 停机
+}
+
+impl std::str::FromStr for Instruction {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        // 从Debug的输出读回指令，供单元的接口文件使用
+        let error = || format!("invalid instruction '{}'", s);
+        let (name, args) = match s.split_once('(') {
+            Some((name, args)) => (name, args.strip_suffix(')').ok_or_else(error)?.split(", ").collect()),
+            None => (s, Vec::new()),
+        };
+        let int = |i: usize| -> Result<i64, String> { args.get(i).and_then(|a| a.parse().ok()).ok_or_else(error) };
+        let ins = match (name, args.len()) {
+            ("Lit", 1) => Instruction::Lit(int(0)?),
+            ("Lod", 2) => Instruction::Lod(int(0)? as i32, int(1)? as i32),
+            ("Sto", 2) => Instruction::Sto(int(0)? as i32, int(1)? as i32),
+            ("Lda", 2) => Instruction::Lda(int(0)? as i32, int(1)? as i32),
+            ("Ldi", 1) => Instruction::Ldi(int(0)? as i32),
+            ("Sti", 1) => Instruction::Sti(int(0)? as i32),
+            ("Idx", 2) => Instruction::Idx(int(0)?, int(1)?),
+            ("Chk", 2) => Instruction::Chk(int(0)?, int(1)?),
+            ("Rng", 2) => Instruction::Rng(int(0)?, int(1)?),
+            ("Opr", 1) => {
+                use Operator::*;
+                let operators = [Plus, Minus, Multiply, Divide, Modulo, And, Or, Not, Equal, NotEqual, Less, LessEqual, Greater, GreaterEqual];
+                let operator = operators.into_iter().find(|o| format!("{:?}", o) == args[0]).ok_or_else(error)?;
+                Instruction::Opr(operator)
+            }
+            ("Neg", 0) => Instruction::Neg,
+            ("Not", 0) => Instruction::Not,
+            ("Jmp", 1) => Instruction::Jmp(int(0)? as usize),
+            ("Jpc", 1) => Instruction::Jpc(int(0)? as usize),
+            ("Jtb", 3) => Instruction::Jtb(int(0)?, int(1)?, int(2)? as usize),
+            ("Cal", 2) => Instruction::Cal(int(0)? as i32, int(1)? as usize),
+            ("Ent", 1) => Instruction::Ent(int(0)? as i32),
+            ("Ret", 2) => Instruction::Ret(int(0)? as i32, args[1] == "true"),
+            ("Red", 0) => Instruction::Red,
+            ("Wrt", 1) => Instruction::Wrt(args[0] == "true"),
+            ("Wrs", 1) => Instruction::Wrs(int(0)? as usize),
+            ("Wln", 0) => Instruction::Wln,
+            ("Hlt", 0) => Instruction::Hlt,
+            _ => return Err(error()),
+        };
+        Ok(ins)
+    }
+}
+
+#[derive(Clone, PartialEq, Debug, Default)]
+pub struct Program {
+    // 目标代码，每条指令对应一个源程序位置，供运行时报错使用
+    pub code: Vec<Instruction>,
+    pub spans: Vec<Span>,
+    pub strings: Vec<String>, // 字符串常量池，相同的字符串只存一份
+    pub externals: Vec<(usize, String, String)>, // 调用其他单元中过程的Cal指令：位置、单元名、过程名，连接时回填入口
+}
+
+impl fmt::Display for Program {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (i, ins) in self.code.iter().enumerate() {
+            writeln!(f, "{:>5} {:?}", i, ins)?;
+        }
+        for (i, s) in self.strings.iter().enumerate() {
+            writeln!(f, "{:>5} {:?}", format!("#{}", i), s)?;
+        }
+        Ok(())
+    }
+}
+
+pub struct Generator {
+    // 代码生成，遍历通过语义检查的抽象语法树，产生栈式虚拟机的指令
+    env: Env, // 符号表栈，记录变量的偏移和过程的入口
+    procedure: String, // 当前所在过程名
+    parameters: Vec<String>, // 当前函数中尚未在函数体里说明的形参
+    short_circuit: bool, // and/or是否短路求值
+    bounds_check: bool, // 是否生成下标和子界的越界检查
+    imported: HashMap<String, String>, // 其他单元中的过程名到单元名
+    forwards: Vec<(String, i32, usize)>, // 定义之前对前置说明的过程的调用：过程名、所在层次、调用指令的位置
+    program: Program,
+}
+
+impl Generator {
+    pub fn new(options: &Options) -> Self {
+        let mut env = Env::new();
+        env.fold = options.language.case_insensitive();
+        env.predefine("maxint", Type::Integer, options.maxint());
+        Generator {
+            env,
+            procedure: "main".to_string(),
+            parameters: Vec::new(),
+            short_circuit: options.short_circuit,
+            bounds_check: options.bounds_check,
+            imported: HashMap::new(),
+            forwards: Vec::new(),
+            program: Program::default(),
+        }
+    }
+    pub fn generate(mut self, ast: &Ast, units: &[Interface]) -> Program {
+        // 主程序作为第0层的过程调用，返回后停机
+        // 对其他单元中过程的调用记在externals中，由连接程序回填
+        let span = Span::default();
+        self.emit(Instruction::Cal(0, 2), span);
+        self.emit(Instruction::Hlt, span);
+        self.env.enter_scope();
+        self.import(ast, units);
+        self.gen_body(&ast.block, 0, false);
+        self.env.exit_scope();
+        self.program
+    }
+    pub fn generate_unit(mut self, ast: &Ast, units: &[Interface]) -> Interface {
+        // 单元没有执行语句，只生成各过程的代码，导出接口部分说明的符号
+        let unit = ast.unit.as_ref().expect("只对单元生成接口");
+        self.env.enter_scope();
+        self.import(ast, units);
+        for d in unit.interface.iter().chain(&ast.block.declarations) {
+            self.gen_declaration(d);
+        }
+        let mut interface = Interface {
+            name: unit.name.clone(),
+            source: 0, // 指纹由写出接口文件的一方填写
+            uses: ast
+                .uses
+                .iter()
+                .map(|(name, _)| (name.clone(), units.iter().find(|u| &u.name == name).map_or(0, Interface::exports)))
+                .collect(),
+            constants: Vec::new(),
+            types: Vec::new(),
+            procedures: Vec::new(),
+            program: Program::default(),
+        };
+        for d in &unit.interface {
+            match d {
+                Declaration::Constant { name, .. } | Declaration::Type { name, .. } | Declaration::Forward { name, .. } => {
+                    match self.env.lookup(name) {
+                        Some(Symbol::Constant(c)) => interface.constants.push(c.clone()),
+                        Some(Symbol::Type(t)) => interface.types.push(t.clone()),
+                        Some(Symbol::Procedure(p)) => interface.procedures.push(p.clone()),
+                        _ => (),
+                    }
+                }
+                _ => (),
+            }
+        }
+        self.env.exit_scope();
+        interface.program = self.program;
+        interface
+    }
+    fn import(&mut self, ast: &Ast, units: &[Interface]) {
+        // 登记直接引用的单元导出的符号
+        for (name, _) in &ast.uses {
+            if let Some(unit) = units.iter().find(|u| &u.name == name) {
+                unit.import(&mut self.env);
+                for p in &unit.procedures {
+                    self.imported.insert(p.pname.clone(), unit.name.clone());
+                }
+            }
+        }
+    }
+
+    fn emit(&mut self, ins: Instruction, span: Span) -> usize {
+        self.program.code.push(ins);
+        self.program.spans.push(span);
+        self.program.code.len() - 1
+    }
+    fn intern(&mut self, value: &str) -> usize {
+        // 在常量池中查找字符串，没有则加入
+        match self.program.strings.iter().position(|s| s == value) {
+            Some(i) => i,
+            None => {
+                self.program.strings.push(value.to_string());
+                self.program.strings.len() - 1
+            }
+        }
+    }
+    fn here(&self) -> usize {
+        self.program.code.len()
+    }
+    fn patch(&mut self, at: usize, target: usize) {
+        // 回填跳转目标和前置说明的过程的入口
+        match &mut self.program.code[at] {
+            Instruction::Jmp(a) | Instruction::Jpc(a) | Instruction::Jtb(_, _, a) | Instruction::Cal(_, a) => *a = target,
+            _ => unreachable!("只回填跳转和调用指令"),
+        }
+    }
+    fn gen_body(&mut self, block: &Block, parameters: i32, function: bool) {
+        // 过程体：开辟空间、嵌套过程的代码、执行语句、返回
+        // for循环的初值和终值也存放在局部变量区，生成完执行语句才知道空间大小
+        let enter = self.emit(Instruction::Ent(0), Span::default());
+        for d in &block.declarations {
+            self.gen_declaration(d);
+        }
+        for s in &block.statements {
+            self.gen_statement(s);
+        }
+        self.program.code[enter] = Instruction::Ent(self.env.frame_size() - RESULT);
+        self.emit(Instruction::Ret(parameters, function), Span::default());
+    }
+    fn gen_declaration(&mut self, declaration: &Declaration) {
+        match declaration {
+            Declaration::Variable { name, vtype, .. } => {
+                // 函数体中对形参的说明，形参已经分配了位置
+                if let Some(i) = self.parameters.iter().position(|p| self.env.same(p, name)) {
+                    self.parameters.remove(i);
+                    return;
+                }
+                let vtype = self.resolve(vtype);
+                self.env.add_variable(name.clone(), self.procedure.clone(), 0, vtype);
+            }
+            Declaration::Constant { name, value, .. } => {
+                // 常量不占空间，使用处折叠为常数
+                let cvalue = value.fold(&|n| self.env.constant(n)).expect("语义检查保证常量的值可以求出");
+                self.env.add_constant(name.clone(), self.type_of(value), cvalue);
+            }
+            Declaration::Type { name, spec, .. } => {
+                let ttype = self.resolve(spec);
+                self.env.add_type(name.clone(), ttype);
+            }
+            Declaration::Forward { name, return_type, parameters, .. } => {
+                // 入口在定义时才知道，之前的调用先记下位置
+                // 调用只用到形参种类，形参类型到定义时才知道，先按integer占位
+                let ptype = vec![Type::Integer; parameters.len()];
+                let pkind = parameters.iter().map(|p| if p.by_reference { 2 } else { 1 }).collect();
+                let return_type = return_type.as_ref().map(|t| self.resolve(t));
+                self.env.add_procedure(name.clone(), return_type, ptype, pkind);
+                if let Some(p) = self.env.find_procedure_mut(name) {
+                    p.pstate = 1;
+                }
+            }
+            Declaration::Function { name, return_type, parameters, body, span } => {
+                // 函数体的代码就地生成，外层执行时跳过
+                let ptype: Vec<Type> = parameters.iter().map(|p| self.resolve(&body.parameter_type(&p.name, &self.env))).collect();
+                let pkind = parameters.iter().map(|p| if p.by_reference { 2 } else { 1 }).collect();
+                let return_type = return_type.as_ref().map(|t| self.resolve(t));
+                self.env.add_procedure(name.clone(), return_type.clone(), ptype.clone(), pkind);
+                let skip = self.emit(Instruction::Jmp(0), *span);
+                let entry = self.here();
+                if let Some(p) = self.env.find_procedure_mut(name) {
+                    p.padr = entry;
+                }
+                // 回填定义之前对前置说明的调用
+                let level = self.env.level();
+                let (calls, rest) = std::mem::take(&mut self.forwards)
+                    .into_iter()
+                    .partition(|(n, l, _)| self.env.same(n, name) && *l == level);
+                self.forwards = rest;
+                for (_, _, at) in calls {
+                    self.patch(at, entry);
+                }
+
+                let outer_procedure = std::mem::replace(&mut self.procedure, name.clone());
+                let outer_parameters = std::mem::take(&mut self.parameters);
+                self.env.enter_scope();
+                for (p, vtype) in parameters.iter().zip(ptype) {
+                    let vkind = if p.by_reference { 2 } else { 1 };
+                    self.env.add_variable(p.name.clone(), name.clone(), vkind, vtype);
+                    self.parameters.push(p.name.clone());
+                }
+                // 语义检查保证形参共占的单元数不超过MAX_SIZE
+                let n = self.env.place_parameters(&self.parameters) as i32;
+                self.gen_body(body, n, return_type.is_some());
+                self.env.exit_scope();
+                self.parameters = outer_parameters;
+                self.procedure = outer_procedure;
+
+                let after = self.here();
+                self.patch(skip, after);
+            }
+        }
+    }
+    fn gen_statement(&mut self, statement: &Statement) {
+        match statement {
+            Statement::Read { target, span } => {
+                self.emit(Instruction::Red, *span);
+                self.gen_store(target, *span);
+            }
+            Statement::Write { arguments, newline, span } => {
+                for a in arguments {
+                    match a {
+                        Expression::String { value, span } => {
+                            let index = self.intern(value);
+                            self.emit(Instruction::Wrs(index), *span);
+                        }
+                        _ => {
+                            let boolean = self.type_of(a) == Type::Boolean;
+                            self.gen_expression(a);
+                            self.emit(Instruction::Wrt(boolean), a.span());
+                        }
+                    }
+                }
+                if *newline {
+                    self.emit(Instruction::Wln, *span);
+                }
+            }
+            Statement::Assign { target, value, span } => {
+                self.gen_expression(value);
+                self.gen_store(target, *span);
+            }
+            Statement::Call { name, arguments, span } => self.gen_call(name, arguments, *span),
+            Statement::If { condition, then_branch, else_branch, span } => {
+                self.gen_expression(condition);
+                let jpc = self.emit(Instruction::Jpc(0), *span);
+                self.gen_statement(then_branch);
+                match else_branch {
+                    Some(else_branch) => {
+                        let jmp = self.emit(Instruction::Jmp(0), *span);
+                        let target = self.here();
+                        self.patch(jpc, target);
+                        self.gen_statement(else_branch);
+                        let target = self.here();
+                        self.patch(jmp, target);
+                    }
+                    None => {
+                        let target = self.here();
+                        self.patch(jpc, target);
+                    }
+                }
+            }
+            Statement::While { condition, body, span } => {
+                let start = self.here();
+                self.gen_expression(condition);
+                let jpc = self.emit(Instruction::Jpc(0), *span);
+                self.gen_statement(body);
+                self.emit(Instruction::Jmp(start), *span);
+                let target = self.here();
+                self.patch(jpc, target);
+            }
+            Statement::Repeat { body, condition, span } => {
+                let start = self.here();
+                for s in body {
+                    self.gen_statement(s);
+                }
+                self.gen_expression(condition);
+                self.emit(Instruction::Jpc(start), *span);
+            }
+            Statement::For { variable, variable_span, start, end, downward, body, span } => {
+                // 初值和终值求值一次，存入两个隐藏的局部单元
+                // 循环不执行时不给控制变量赋值；执行时控制变量到达或越过终值后不再步进，
+                // 循环结束后控制变量的值就是终值，也不会因步进而溢出
+                let control = Expression::Variable { name: variable.clone(), span: *variable_span };
+                let first = self.env.allocate(2);
+                let last = first + 1;
+                let (compare, more, step) = match downward {
+                    false => (Operator::LessEqual, Operator::Less, Operator::Plus),
+                    true => (Operator::GreaterEqual, Operator::Greater, Operator::Minus),
+                };
+                self.gen_expression(start);
+                self.emit(Instruction::Sto(0, first), *span);
+                self.gen_expression(end);
+                self.emit(Instruction::Sto(0, last), *span);
+                self.emit(Instruction::Lod(0, first), *span);
+                self.emit(Instruction::Lod(0, last), *span);
+                self.emit(Instruction::Opr(compare), *span);
+                let skip = self.emit(Instruction::Jpc(0), *span);
+                self.emit(Instruction::Lod(0, first), *span);
+                self.gen_store(&control, *span);
+                let top = self.here();
+                self.gen_statement(body);
+                self.gen_load(&control);
+                self.emit(Instruction::Lod(0, last), *span);
+                self.emit(Instruction::Opr(more), *span);
+                let done = self.emit(Instruction::Jpc(0), *span);
+                self.gen_load(&control);
+                self.emit(Instruction::Lit(1), *span);
+                self.emit(Instruction::Opr(step), *span);
+                self.gen_store(&control, *span);
+                self.emit(Instruction::Jmp(top), *span);
+                let target = self.here();
+                self.patch(skip, target);
+                self.patch(done, target);
+            }
+            Statement::Case { selector, arms, otherwise, span } => {
+                // 选择表达式求值一次，存入一个隐藏的局部单元
+                // 标号密集时用跳转表，稀疏时逐个比较；没有匹配的标号又没有else时什么也不做
+                let slot = self.env.allocate(1);
+                self.gen_expression(selector);
+                self.emit(Instruction::Sto(0, slot), *span);
+                // 标号可以靠近±maxint，个数和跨度按i128计算，跨度太大时用比较链
+                let labels = self.case_labels(arms);
+                let covered: i128 = labels.iter().map(|&(low, high, _)| high as i128 - low as i128 + 1).sum();
+                let low = labels.iter().map(|l| l.0).min().unwrap_or(0);
+                let high = labels.iter().map(|l| l.1).max().unwrap_or(0);
+                let width = high as i128 - low as i128 + 1;
+                let mut tests = Vec::new(); // 比较链中各条件跳转的位置和目标分支
+                let dispatch = match covered >= 4 && width <= 2 * covered && width <= MAX_TABLE as i128 {
+                    true => {
+                        // 跳转表：每个值对应一条跳转指令，未出现的值跳到缺省目标
+                        self.emit(Instruction::Lod(0, slot), *span);
+                        let jtb = self.emit(Instruction::Jtb(low, high, 0), *span);
+                        let table = self.here();
+                        for _ in 0..width {
+                            self.emit(Instruction::Jmp(0), *span);
+                        }
+                        (Some(jtb), table)
+                    }
+                    false => {
+                        // 比较链：值等于标号或落在标号范围内时跳到对应分支
+                        for &(l, h, arm) in &labels {
+                            self.emit(Instruction::Lod(0, slot), *span);
+                            match l == h {
+                                true => {
+                                    self.emit(Instruction::Lit(l), *span);
+                                    self.emit(Instruction::Opr(Operator::NotEqual), *span);
+                                }
+                                false => {
+                                    self.emit(Instruction::Lit(l), *span);
+                                    self.emit(Instruction::Opr(Operator::Less), *span);
+                                    self.emit(Instruction::Lod(0, slot), *span);
+                                    self.emit(Instruction::Lit(h), *span);
+                                    self.emit(Instruction::Opr(Operator::Greater), *span);
+                                    self.emit(Instruction::Opr(Operator::Or), *span);
+                                }
+                            }
+                            let jpc = self.emit(Instruction::Jpc(0), *span);
+                            tests.push((jpc, arm));
+                        }
+                        (None, self.emit(Instruction::Jmp(0), *span))
+                    }
+                };
+                let mut starts = Vec::new();
+                let mut exits = Vec::new();
+                for arm in arms {
+                    starts.push(self.here());
+                    self.gen_statement(&arm.body);
+                    exits.push(self.emit(Instruction::Jmp(0), *span));
+                }
+                let default = self.here();
+                for s in otherwise.iter().flatten() {
+                    self.gen_statement(s);
+                }
+                let end = self.here();
+                match dispatch {
+                    (Some(jtb), table) => {
+                        self.patch(jtb, default);
+                        // 用跳转表时跨度不超过MAX_TABLE
+                        for i in 0..width as i64 {
+                            let target = labels.iter()
+                                .find(|&&(l, h, _)| l <= low + i && low + i <= h)
+                                .map_or(default, |&(_, _, arm)| starts[arm]);
+                            self.patch(table + i as usize, target);
+                        }
+                    }
+                    (None, jmp) => {
+                        for (at, arm) in tests {
+                            self.patch(at, starts[arm]);
+                        }
+                        self.patch(jmp, default);
+                    }
+                }
+                for at in exits {
+                    self.patch(at, end);
+                }
+            }
+            Statement::Compound { statements, .. } => {
+                for s in statements {
+                    self.gen_statement(s);
+                }
+            }
+            Statement::Empty { .. } => (),
+        }
+    }
+    fn case_labels(&self, arms: &[CaseArm]) -> Vec<(i64, i64, usize)> {
+        // 情况语句各标号的下界、上界和所在分支的序号，标号在语义检查时已确认是常量
+        let mut labels = Vec::new();
+        for (i, arm) in arms.iter().enumerate() {
+            for label in &arm.labels {
+                let low = label.low.fold(&|n| self.env.constant(n)).unwrap_or(0);
+                let high = match &label.high {
+                    Some(high) => high.fold(&|n| self.env.constant(n)).unwrap_or(low),
+                    None => low,
+                };
+                labels.push((low, high, i));
+            }
+        }
+        labels
+    }
+    fn gen_expression(&mut self, expression: &Expression) {
+        if let Some(value) = expression.fold(&|n| self.env.constant(n)) {
+            self.emit(Instruction::Lit(value), expression.span());
+            return;
+        }
+        match expression {
+            Expression::Variable { .. } | Expression::Index { .. } | Expression::Field { .. } => self.gen_load(expression),
+            Expression::Literal { value, span } => {
+                self.emit(Instruction::Lit(*value), *span);
+            }
+            Expression::Boolean { value, span } => {
+                self.emit(Instruction::Lit(*value as i64), *span);
+            }
+            Expression::String { .. } => unreachable!("语义检查保证字符串常量只出现在写语句中"),
+            Expression::Call { name, arguments, span } => self.gen_call(name, arguments, *span),
+            Expression::Unary { operator, operand, span } => {
+                self.gen_expression(operand);
+                match operator {
+                    Operator::Minus => {
+                        self.emit(Instruction::Neg, *span);
+                    }
+                    Operator::Not => {
+                        self.emit(Instruction::Not, *span);
+                    }
+                    _ => (),
+                }
+            }
+            Expression::Binary { operator: Operator::And, left, right, span } if self.short_circuit => {
+                // 短路求值：左操作数为假时不再计算右操作数
+                self.gen_expression(left);
+                let jpc = self.emit(Instruction::Jpc(0), *span);
+                self.gen_expression(right);
+                let jmp = self.emit(Instruction::Jmp(0), *span);
+                let target = self.here();
+                self.patch(jpc, target);
+                self.emit(Instruction::Lit(0), *span);
+                let target = self.here();
+                self.patch(jmp, target);
+            }
+            Expression::Binary { operator: Operator::Or, left, right, span } if self.short_circuit => {
+                // 短路求值：左操作数为真时不再计算右操作数
+                self.gen_expression(left);
+                let jpc = self.emit(Instruction::Jpc(0), *span);
+                self.emit(Instruction::Lit(1), *span);
+                let jmp = self.emit(Instruction::Jmp(0), *span);
+                let target = self.here();
+                self.patch(jpc, target);
+                self.gen_expression(right);
+                let target = self.here();
+                self.patch(jmp, target);
+            }
+            Expression::Binary { operator, left, right, span } => {
+                self.gen_expression(left);
+                self.gen_expression(right);
+                self.emit(Instruction::Opr(*operator), *span);
+            }
+        }
+    }
+    fn gen_call(&mut self, name: &str, arguments: &[Expression], span: Span) {
+        // 值形参压入实参的值，变量形参压入实参的地址
+        // 其他单元中的过程按单元中的拼写记录，不区分大小写时与调用处的拼写可能不同
+        let (plev, padr, pstate, pkind, pname) = match self.env.lookup(name) {
+            Some(Symbol::Procedure(p)) => (p.plev, p.padr, p.pstate, p.pkind.clone(), p.pname.clone()),
+            _ => unreachable!("语义检查保证调用的是过程或函数"),
+        };
+        for (a, kind) in arguments.iter().zip(pkind) {
+            match kind {
+                2 => self.gen_address(a),
+                _ => self.gen_expression(a),
+            }
+        }
+        let level = self.env.level();
+        let at = self.emit(Instruction::Cal(level - plev, padr), span);
+        match pstate {
+            1 => self.forwards.push((name.to_string(), plev, at)),
+            2 => self.program.externals.push((at, self.imported[&pname].clone(), pname)),
+            _ => (),
+        }
+    }
+    fn gen_load(&mut self, target: &Expression) {
+        // 变量、数组元素或记录的域的值入栈，数组和记录的值占多个单元
+        if let Expression::Variable { name, span } = target {
+            let (level, offset, by_reference) = self.address(name);
+            if !by_reference && self.type_of(target).is_simple() {
+                self.emit(Instruction::Lod(level, offset), *span);
+                return;
+            }
+        }
+        let size = self.type_of(target).size() as i32;
+        self.gen_address(target);
+        self.emit(Instruction::Ldi(size), target.span());
+    }
+    fn gen_store(&mut self, target: &Expression, span: Span) {
+        // 栈顶的值存入变量、数组元素或记录的域，存入子界变量前检查是否越界
+        if let Type::Subrange { low, high } = self.type_of(target)
+            && self.bounds_check
+        {
+            self.emit(Instruction::Rng(low, high), span);
+        }
+        if let Expression::Variable { name, .. } = target {
+            let (level, offset, by_reference) = self.address(name);
+            if !by_reference && self.type_of(target).is_simple() {
+                self.emit(Instruction::Sto(level, offset), span);
+                return;
+            }
+        }
+        let size = self.type_of(target).size() as i32;
+        self.gen_address(target);
+        self.emit(Instruction::Sti(size), span);
+    }
+    fn gen_address(&mut self, target: &Expression) {
+        // 变量、数组元素或记录的域的地址入栈，变量形参本身存放的就是地址
+        match target {
+            Expression::Variable { name, span } => {
+                let (level, offset, by_reference) = self.address(name);
+                match by_reference {
+                    true => self.emit(Instruction::Lod(level, offset), *span),
+                    false => self.emit(Instruction::Lda(level, offset), *span),
+                };
+            }
+            Expression::Index { array, index, span } => {
+                let (low, high, size) = match self.type_of(array) {
+                    Type::Array { low, high, element } => (low, high, element.size()),
+                    _ => unreachable!("语义检查保证只对数组使用下标"),
+                };
+                self.gen_address(array);
+                self.gen_expression(index);
+                if self.bounds_check {
+                    self.emit(Instruction::Chk(low, high), *span);
+                }
+                self.emit(Instruction::Idx(low, size), *span);
+            }
+            Expression::Field { record, field, span } => {
+                // 域的地址是记录的地址加上域的偏移
+                let offset = match self.type_of(record).field(field, &self.env) {
+                    Some((offset, _)) => offset,
+                    None => unreachable!("语义检查保证记录中有该域"),
+                };
+                self.gen_address(record);
+                if offset != 0 {
+                    self.emit(Instruction::Lit(offset), *span);
+                    self.emit(Instruction::Opr(Operator::Plus), *span);
+                }
+            }
+            _ => unreachable!("语义检查保证只对变量取地址"),
+        }
+    }
+    fn resolve(&self, vtype: &TypeSpec) -> Type {
+        vtype.resolve(&self.env).expect("语义检查保证类型可以求出")
+    }
+    fn type_of(&self, expression: &Expression) -> Type {
+        // 求变量和数组元素的类型，其余表达式都是简单类型，只区分是否为布尔值
+        match expression {
+            Expression::Variable { name, .. } => match self.env.lookup(name) {
+                Some(Symbol::Variable(v)) => v.vtype.clone(),
+                Some(Symbol::Constant(c)) => c.ctype.clone(),
+                Some(Symbol::Type(_)) => unreachable!("语义检查保证类型名不作为变量使用"),
+                Some(Symbol::Procedure(p)) => p.preturn.clone().unwrap_or(Type::Integer),
+                None => unreachable!("语义检查保证符号已声明"),
+            },
+            Expression::Index { array, .. } => match self.type_of(array) {
+                Type::Array { element, .. } => *element,
+                _ => unreachable!("语义检查保证只对数组使用下标"),
+            },
+            Expression::Field { record, field, .. } => match self.type_of(record).field(field, &self.env) {
+                Some((_, ftype)) => ftype.clone(),
+                None => unreachable!("语义检查保证记录中有该域"),
+            },
+            Expression::Call { name, .. } => match self.env.lookup(name) {
+                Some(Symbol::Procedure(p)) => p.preturn.clone().unwrap_or(Type::Integer),
+                _ => unreachable!("语义检查保证调用的是函数"),
+            },
+            Expression::Boolean { .. } => Type::Boolean,
+            Expression::String { .. } => Type::String,
+            Expression::Unary { operator: Operator::Not, .. } => Type::Boolean,
+            Expression::Binary { operator, .. } => match operator {
+                Operator::Plus | Operator::Minus | Operator::Multiply | Operator::Divide | Operator::Modulo => Type::Integer,
+                _ => Type::Boolean,
+            },
+            _ => Type::Integer,
+        }
+    }
+    fn address(&self, name: &str) -> (i32, i32, bool) {
+        self.env.address(name).expect("语义检查保证只对已声明的变量或函数结果取地址")
+    }
+}
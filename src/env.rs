@@ -1,4 +1,8 @@
+use crate::code::{FIRST_LOCAL, RESULT};
+use crate::lex::Span;
+use crate::types::{Type, MAX_SIZE};
 use std::collections::HashMap;
+use std::fmt;
 
 #[derive(Clone, PartialEq, Debug)]
 pub enum Token {
@@ -8,10 +12,14 @@ pub enum Token {
     
     // 字面量
     IntegerLiteral(i64),
+    StringLiteral(String), // 单引号括起的字符串，已去掉引号并还原 ''
 
     // 算术运算符
+    Plus,
     Minus,
     Multiply,
+    Div, // 关键字div，整除
+    Mod, // 关键字mod，取余
     Assign,
 
     // 关系运算符
@@ -28,6 +36,12 @@ pub enum Token {
     LeftParenthesis,
     RightParenthesis,
     Semicolon,
+    Comma,
+    LeftBracket,
+    RightBracket,
+    Range, // 子界中的 ..
+    Dot, // 记录的域选择
+    Colon, // 情况语句中标号之后的冒号
 
     // 行末提示符
     Eol,
@@ -43,18 +57,45 @@ pub enum Token {
     Else,
     Read,
     Write,
+    Writeln,
+    While,
+    Do,
+    Repeat,
+    Until,
+    Boolean,
+    True,
+    False,
+    And,
+    Or,
+    Not,
+    Procedure,
+    Var,
+    Array,
+    Of,
+    Const,
+    Type,
+    Record,
+    For,
+    To,
+    Downto,
+    Case,
+    Forward,
+    Unit,
+    Interface,
+    Implementation,
+    Uses,
+
+    // 注释，作为附加信息保留，语法分析时跳过
+    Comment(String),
 
     // 非法字符
     Illegal(char),
 }
 
-#[derive(Clone)]
+#[derive(Clone, PartialEq, Debug)]
 pub enum ErrorMessage {
     // 所有的报错信息
     SyntaxError,// 语法错误
-    WrongReserveYouMeanFunction, // wrong reserve: you mean 'function'?
-    WrongReserveYouMeanRead, // wrong reserve: you mean 'read'?
-    WrongReserveYouMeanWrite, // wrong reserve: you mean 'write'?
     WrongAssignToken, // wrong assign operator: you mean ':='?
     InvalidTypeExpectedInterger, // 非法的类型，expected integer
     InvalidNumber, // 非法数字串
@@ -66,72 +107,283 @@ pub enum ErrorMessage {
     MissingIf, // expected 'if' 
     MissingThen, // expected 'then' 
     MissingElse, // expected 'else'
+    MissingDo, // expected 'do'
+    MissingUntil, // expected 'until'
     MissingMultiply, // expected 'multiply'
     SyntaxErrorExpectedABlock, // expected a block
     FailMatching, // 符号匹配错误
     MissingEnd, // begin没有匹配的end
     ExpectedIdentifier, // 符号无声明
     FoundRepeatDeclarationInThisField, //符号重复声明
+    UnterminatedComment, // 注释没有闭合
+    UnterminatedString, // 字符串在行末之前没有闭合
+    UndeclaredIdentifier(String), // 使用了未声明的符号
+    DivisionByZero, // 除数为0，编译时或运行时发现
+    IntegerOverflow, // 运行时整数溢出
+    InvalidInput, // 运行时读入的不是整数
+    StackOverflow, // 运行时栈溢出，通常是递归过深
+    TypeMismatch(Type, Type), // 类型不匹配：期望的类型，实际的类型
+    ArgumentCountMismatch(usize, usize), // 实参个数与形参不符：形参个数，实参个数
+    NotAFunction(String), // 调用的符号不是函数
+    NotAProcedure(String), // 调用语句调用的符号不是过程
+    ExpectedVariableArgument, // 变量形参对应的实参不是变量
+    MissingRightBracket, // expected ']'
+    MissingOf, // expected 'of'
+    MissingRange, // expected '..'
+    InvalidBounds(i64, i64), // 数组或子界的下界大于上界
+    NotAnArray, // 对非数组使用下标
+    IndexOutOfBounds(i64, i64, i64), // 下标越界：下标，下界，上界
+    InvalidReturnType(Type), // 函数返回值不是简单类型
+    AddressOutOfRange(i64), // 关闭下标检查后访问了运行栈以外的地址
+    ExpectedConstant, // 需要常量表达式
+    AssignToConstant(String), // 给常量赋值
+    NotAType(String), // 用作类型的符号不是类型
+    NotAVariable(String), // 类型名用作变量
+    NotARecord, // 对非记录选择域
+    NoSuchField(String), // 记录中没有该域
+    ValueOutOfRange(i64, i64, i64), // 值超出子界：值，下界，上界
+    MissingTo, // expected 'to' or 'downto'
+    InvalidLoopVariable(String), // for循环的控制变量不是integer或子界变量
+    AssignToLoopVariable(String), // 在循环体内给控制变量赋值
+    MissingColon, // expected ':'
+    DuplicateCaseLabel(i64), // 情况标号重复或范围重叠
+    ResultOutsideFunction(String), // 在函数体以外使用函数的结果单元
+    TypeTooLarge, // 类型的单元数超过MAX_SIZE
+    FrameTooLarge(String), // 过程的局部变量或形参超过MAX_SIZE个单元
+    ParameterTypeInBody(String), // 形参的类型用到了函数体中说明的类型或常量
+    ForwardMismatch(String), // 过程的定义与前置说明不一致
+    UndefinedForward(String), // 前置说明的过程没有定义
+    MissingInterface, // expected 'interface'
+    MissingImplementation, // expected 'implementation'
+    VariableInUnit, // 单元中说明了变量
+    UnitNotFound(String), // 在搜索路径中找不到单元
+    NotAUnit(String), // 引用的文件不是单元，或单元名与文件名不符
+    CircularUnit(String), // 单元之间循环引用：引用链
+    UnitHasErrors(String), // 引用的单元有编译错误
+    InvalidInterfaceFile(String), // 接口文件无法读取或格式错误
+    IncludeNotFound(String), // 找不到包含的文件
+    CircularInclude(String), // 文件之间循环包含：包含链
+    UnknownDirective(String), // 不认识的编译指令
+    UnmatchedDirective(String), // {$ELSE}或{$ENDIF}没有对应的{$IFDEF}
+    UnterminatedConditional, // {$IFDEF}到文件结束也没有{$ENDIF}
+    InvalidEncoding, // 源文件不是UTF-8、UTF-16或GBK编码
+    LiteralOverflow(i64), // 整数常量超过maxint
+    ConstantOverflow(i64), // 常量表达式的值超出integer的范围
+    DidYouMean(Box<ErrorMessage>, String), // 报错信息，附上拼写相近的关键字或符号名
 }
 
+impl fmt::Display for ErrorMessage {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ErrorMessage::SyntaxError => write!(f, "unknown token!"),
+            ErrorMessage::WrongAssignToken => write!(f, "wrong assign operator: you mean ':='?"),
+            ErrorMessage::InvalidTypeExpectedInterger => write!(f, "invalid type: expected INTEGER"),
+            ErrorMessage::InvalidNumber => write!(f, "Invalid number!"),
+            ErrorMessage::OverflowIdentifier => write!(f, "Identifier length overflow!"),
+            ErrorMessage::FailMatchingSemicolon => write!(f, "Semicolon matching failed!"),
+            ErrorMessage::MissingSemicolon => write!(f, "missing a ';' at the end of the statement"),
+            ErrorMessage::MissingLeftParenthesis => write!(f, "expected '(' following the function statement"),
+            ErrorMessage::MissingRightParenthesis => write!(f, "expected ')' to cover the block"),
+            ErrorMessage::MissingIf => write!(f, "expected 'if'"),
+            ErrorMessage::MissingThen => write!(f, "expected 'then'"),
+            ErrorMessage::MissingElse => write!(f, "expected 'else'"),
+            ErrorMessage::MissingDo => write!(f, "expected 'do'"),
+            ErrorMessage::MissingUntil => write!(f, "expected 'until'"),
+            ErrorMessage::MissingMultiply => write!(f, "expected '*'"),
+            ErrorMessage::SyntaxErrorExpectedABlock => write!(f, "syntax error, expected a block"),
+            ErrorMessage::FailMatching => write!(f, "Symbol matching error!"),
+            ErrorMessage::MissingEnd => write!(f, "missing END: this block is not covered"),
+            ErrorMessage::ExpectedIdentifier => write!(f, "Expected identifier in this field"),
+            ErrorMessage::FoundRepeatDeclarationInThisField => write!(f, "this symbol's declaration repeated in this field"),
+            ErrorMessage::UnterminatedString => write!(f, "unterminated string"),
+            ErrorMessage::UnterminatedComment => write!(f, "unterminated comment starting here"),
+            ErrorMessage::UndeclaredIdentifier(name) => write!(f, "undeclared identifier '{}'", name),
+            ErrorMessage::DivisionByZero => write!(f, "division by zero"),
+            ErrorMessage::IntegerOverflow => write!(f, "integer overflow"),
+            ErrorMessage::InvalidInput => write!(f, "invalid input: expected an integer"),
+            ErrorMessage::StackOverflow => write!(f, "stack overflow"),
+            ErrorMessage::TypeMismatch(expected, found) => write!(f, "type mismatch: expected {}, found {}", expected, found),
+            ErrorMessage::ArgumentCountMismatch(expected, found) => write!(f, "wrong number of arguments: expected {}, found {}", expected, found),
+            ErrorMessage::NotAFunction(name) => write!(f, "'{}' is not a function", name),
+            ErrorMessage::NotAProcedure(name) => write!(f, "'{}' is not a procedure", name),
+            ErrorMessage::ExpectedVariableArgument => write!(f, "argument for a var parameter must be a variable"),
+            ErrorMessage::MissingRightBracket => write!(f, "expected ']'"),
+            ErrorMessage::MissingOf => write!(f, "expected 'of'"),
+            ErrorMessage::MissingRange => write!(f, "expected '..'"),
+            ErrorMessage::InvalidBounds(low, high) => write!(f, "invalid bounds {}..{}", low, high),
+            ErrorMessage::NotAnArray => write!(f, "indexed value is not an array"),
+            ErrorMessage::IndexOutOfBounds(index, low, high) => write!(f, "index {} out of bounds {}..{}", index, low, high),
+            ErrorMessage::InvalidReturnType(t) => write!(f, "function result must be integer or boolean, found {}", t),
+            ErrorMessage::ExpectedConstant => write!(f, "constant expression expected"),
+            ErrorMessage::AssignToConstant(name) => write!(f, "cannot assign to constant '{}'", name),
+            ErrorMessage::NotAType(name) => write!(f, "'{}' is not a type", name),
+            ErrorMessage::NotAVariable(name) => write!(f, "'{}' is a type, not a variable", name),
+            ErrorMessage::NotARecord => write!(f, "selected value is not a record"),
+            ErrorMessage::NoSuchField(name) => write!(f, "record has no field '{}'", name),
+            ErrorMessage::ValueOutOfRange(v, low, high) => write!(f, "value {} out of range {}..{}", v, low, high),
+            ErrorMessage::MissingTo => write!(f, "expected 'to' or 'downto'"),
+            ErrorMessage::InvalidLoopVariable(name) => write!(f, "'{}' cannot be used as a for loop variable", name),
+            ErrorMessage::AssignToLoopVariable(name) => write!(f, "cannot assign to for loop variable '{}'", name),
+            ErrorMessage::MissingColon => write!(f, "expected ':'"),
+            ErrorMessage::DuplicateCaseLabel(v) => write!(f, "duplicate case label {}", v),
+            ErrorMessage::ForwardMismatch(name) => write!(f, "definition of '{}' does not match its forward declaration", name),
+            ErrorMessage::UndefinedForward(name) => write!(f, "forward declared '{}' is never defined", name),
+            ErrorMessage::MissingInterface => write!(f, "expected 'interface'"),
+            ErrorMessage::MissingImplementation => write!(f, "expected 'implementation'"),
+            ErrorMessage::VariableInUnit => write!(f, "variables cannot be declared in a unit"),
+            ErrorMessage::UnitNotFound(name) => write!(f, "unit '{}' not found", name),
+            ErrorMessage::NotAUnit(name) => write!(f, "'{}' is not a unit of that name", name),
+            ErrorMessage::CircularUnit(chain) => write!(f, "circular unit reference: {}", chain),
+            ErrorMessage::UnitHasErrors(name) => write!(f, "unit '{}' has errors", name),
+            ErrorMessage::InvalidInterfaceFile(path) => write!(f, "invalid interface file {}", path),
+            ErrorMessage::IncludeNotFound(name) => write!(f, "include file '{}' not found", name),
+            ErrorMessage::CircularInclude(chain) => write!(f, "circular include: {}", chain),
+            ErrorMessage::UnknownDirective(name) => write!(f, "unknown directive '{{${}}}'", name),
+            ErrorMessage::UnmatchedDirective(name) => write!(f, "'{{${}}}' without matching '{{$IFDEF}}'", name),
+            ErrorMessage::UnterminatedConditional => write!(f, "'{{$IFDEF}}' without matching '{{$ENDIF}}'"),
+            ErrorMessage::DidYouMean(message, suggestion) => write!(f, "{}; did you mean '{}'?", message, suggestion),
+            ErrorMessage::LiteralOverflow(maxint) => write!(f, "integer literal exceeds maxint ({})", maxint),
+            ErrorMessage::ConstantOverflow(maxint) => write!(f, "constant expression overflows integer (maxint = {})", maxint),
+            ErrorMessage::InvalidEncoding => write!(f, "source is not valid UTF-8, UTF-16 or GBK text, undecodable bytes replaced"),
+            ErrorMessage::ResultOutsideFunction(name) => write!(f, "result of function '{}' used outside its body", name),
+            ErrorMessage::AddressOutOfRange(a) => write!(f, "memory access out of range at address {}", a),
+            ErrorMessage::ParameterTypeInBody(name) => write!(f, "type of parameter '{}' must not use types or constants declared in the function body", name),
+            ErrorMessage::TypeTooLarge => write!(f, "type is too large (more than {} cells)", MAX_SIZE),
+            ErrorMessage::FrameTooLarge(name) => write!(f, "variables of '{}' need more than {} cells", name, MAX_SIZE),
+        }
+    }
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct Diagnostic {
+    // 一条报错信息及其在源程序中的位置
+    pub message: ErrorMessage,
+    pub span: Span,
+}
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "LINE{}: {}", self.span.line, self.message)
+    }
+}
+
+pub type Diagnostics = Vec<Diagnostic>;
+
 #[derive(Clone)]
 pub struct VariableItem {
     // 变量表项
     pub vname: String, // 变量名
     pub vproc: String, // 所属过程
-    pub vkind: i32, // 0-变量，1-形参
+    pub vkind: i32, // 0-变量，1-值形参，2-变量形参（按引用传递，存放实参的地址）
     pub vlev: i32, // 变量所在层次
-    // pub vadr: i32, // 相对于第一个变量在变量表中的位置
-    pub vtype: Vec<i32>, // 变量类型 
+    pub vadr: i32, // 在活动记录中相对于基址的偏移，说明时分配
+    pub vtype: Type, // 变量类型
+    pub vreadonly: bool, // 是否只读，for循环的控制变量在循环体内只读
 }
 impl VariableItem {
-    pub fn new(vname: String, vproc: String, vkind: i32, vlev: i32)-> Self{
-        let v = VariableItem {
-            vname: vname,
-            vproc: vproc,
-            vkind: vkind,
-            vlev: vlev,
-            vtype: Vec::new(),
-        };
-        v
+    pub fn new(vname: String, vproc: String, vkind: i32, vlev: i32, vtype: Type)-> Self{
+        VariableItem {
+            vname,
+            vproc,
+            vkind,
+            vlev,
+            vadr: 0,
+            vtype,
+            vreadonly: false,
+        }
     }
 }
 
-#[derive(Clone)]
+#[derive(Clone, PartialEq, Debug)]
 pub struct ProcedureItem {
     // 过程表项
     pub pname: String, // 过程名
-    pub ptype: Vec<i32>, //过程类型
+    pub ptype: Vec<Type>, // 各形参的类型
+    pub pkind: Vec<i32>, // 各形参的种类，与vkind相同：1-值形参，2-变量形参
     pub plev: i32, // 过程所在层次
+    pub preturn: Option<Type>, // 返回值类型，过程没有返回值
+    pub padr: usize, // 过程入口在目标代码中的地址，由代码生成填写
+    pub pstate: i32, // 0-已定义，1-只有前置说明，定义之前调用处的入口地址待回填，2-其他单元中的过程，入口在连接时回填
     // pub fadr: i32, // 第一个变量在变量表里的位置
     // pub ladr: i32, // 最后一个变量在变量表中的位置
 }
 impl ProcedureItem {
-    pub fn new(pname: String, plev: i32) -> Self {
-        let p = ProcedureItem {
-            pname: pname, 
-            ptype: Vec::new(),
-            plev: plev
-        };
-        p
+    pub fn new(pname: String, plev: i32, preturn: Option<Type>, ptype: Vec<Type>, pkind: Vec<i32>) -> Self {
+        ProcedureItem {
+            pname, 
+            ptype,
+            pkind,
+            plev,
+            preturn,
+            padr: 0,
+            pstate: 0,
+        }
     }
 }
 
+#[derive(Clone, PartialEq, Debug)]
+pub struct ConstantItem {
+    // 常量表项
+    pub cname: String, // 常量名
+    pub clev: i32, // 常量所在层次
+    pub ctype: Type, // 常量类型
+    pub cvalue: i64, // 编译时求出的值，布尔常量为1或0
+}
+impl ConstantItem {
+    pub fn new(cname: String, clev: i32, ctype: Type, cvalue: i64) -> Self {
+        ConstantItem {
+            cname,
+            clev,
+            ctype,
+            cvalue,
+        }
+    }
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct TypeItem {
+    // 类型表项，类型名是其定义的别名，类型按结构比较
+    pub tname: String, // 类型名
+    pub tlev: i32, // 类型所在层次
+    pub ttype: Type, // 定义的类型
+}
+impl TypeItem {
+    pub fn new(tname: String, tlev: i32, ttype: Type) -> Self {
+        TypeItem {
+            tname,
+            tlev,
+            ttype,
+        }
+    }
+}
+
+pub enum Symbol<'a> {
+    // 按名字查到的符号
+    Variable(&'a VariableItem),
+    Procedure(&'a ProcedureItem),
+    Constant(&'a ConstantItem),
+    Type(&'a TypeItem),
+}
+
 #[derive(Clone)]
 pub struct SymbolTable {
     // 符号表,每个作用域都应该对应一个符号表
     pub variables: HashMap<String, VariableItem>, // 变量表
     pub procedures: HashMap<String, ProcedureItem>, // 过程表
+    pub constants: HashMap<String, ConstantItem>, // 常量表
+    pub types: HashMap<String, TypeItem>, // 类型表
     pub level: i32, // 当前作用域层级
+    pub next: i32, // 活动记录中下一个空闲单元的偏移
 }
 impl SymbolTable {
     pub fn new(level: i32) -> Self {
-        let st = SymbolTable {
+        SymbolTable {
             variables: HashMap::new(),
             procedures: HashMap::new(),
-            level: level,
-        };
-        st
+            constants: HashMap::new(),
+            types: HashMap::new(),
+            level,
+            next: FIRST_LOCAL,
+        }
     }
     pub fn get_level(&self) -> i32{
         self.level
@@ -142,58 +394,253 @@ impl SymbolTable {
 pub struct Env {
     // 符号表栈，管理顶层符号表随作用域变化
     pub stack: Vec<SymbolTable>,
+    pub trace: bool, // 是否在标准错误流打印作用域变化和符号声明
+    pub fold: bool, // 符号名是否不区分大小写，表项中保留声明时的拼写
+    pub predefined: Vec<ConstantItem>, // 预定义的常量，在所有作用域之外，可以被同名的说明遮蔽
+}
+impl Default for Env {
+    fn default() -> Self {
+        Self::new()
+    }
 }
+
 impl Env {
     pub fn new() -> Self {
-        let e = Env {
+        Env {
             stack: Vec::new(),
-        };
-        e
+            trace: false,
+            fold: false,
+            predefined: Vec::new(),
+        }
+    }
+    pub fn visible(&self) -> Vec<&str> {
+        // 当前可见的全部符号名，按声明时的拼写，供拼写建议使用
+        let mut names: Vec<&str> = self.predefined.iter().map(|c| c.cname.as_str()).collect();
+        for s in &self.stack {
+            names.extend(s.variables.values().map(|v| v.vname.as_str()));
+            names.extend(s.procedures.values().map(|p| p.pname.as_str()));
+            names.extend(s.constants.values().map(|c| c.cname.as_str()));
+            names.extend(s.types.values().map(|t| t.tname.as_str()));
+        }
+        names
+    }
+    pub fn predefine(&mut self, cname: &str, ctype: Type, cvalue: i64) {
+        // 登记一个预定义的常量，如maxint
+        self.predefined.push(ConstantItem::new(cname.to_string(), 0, ctype, cvalue));
+    }
+    fn key(&self, name: &str) -> String {
+        // 符号表中的键，不区分大小写时统一为小写
+        match self.fold {
+            true => name.to_lowercase(),
+            false => name.to_string(),
+        }
+    }
+    pub fn same(&self, a: &str, b: &str) -> bool {
+        // 两个名字是否指同一符号，不区分大小写时忽略大小写
+        self.key(a) == self.key(b)
     }
     pub fn enter_scope(&mut self){
         // 进入作用域，移入一个空符号表
         let t = SymbolTable::new(self.stack.len() as i32);
+        if self.trace {
+            eprintln!("[env] enter scope, level {}", t.get_level());
+        }
         self.stack.push(t);
     }
     pub fn exit_scope(&mut self){
         // 退出作用域，移出栈顶符号表
-        self.stack.pop();
+        if let Some(t) = self.stack.pop()
+            && self.trace
+        {
+            eprintln!("[env] exit scope, level {}", t.get_level());
+        }
     }
-    pub fn add_variable(&mut self, vname: String, vproc: String, vkind: i32){
-        // 声明一个变量
+    pub fn add_variable(&mut self, vname: String, vproc: String, vkind: i32, vtype: Type){
+        // 声明一个变量，局部变量在活动记录中依次分配单元，形参由place_parameters分配
+        let key = self.key(&vname);
         let t: &mut SymbolTable = self.stack.last_mut().unwrap();
-        let item = VariableItem::new(vname.clone(), vproc, vkind, t.get_level());
-        t.variables.insert(vname, item);
+        let mut item = VariableItem::new(vname.clone(), vproc, vkind, t.get_level(), vtype);
+        if vkind == 0 {
+            // 活动记录放不下时已在语义检查时报错，不再分配单元
+            item.vadr = t.next;
+            if let Some(next) = i32::try_from(item.vtype.size()).ok().and_then(|n| t.next.checked_add(n)) {
+                t.next = next;
+            }
+        }
+        if self.trace {
+            let offset = if vkind == 0 { format!(", offset {}", item.vadr) } else { String::new() };
+            eprintln!("[env] variable {} (proc {}, kind {}, level {}{}, type {})", item.vname, item.vproc, item.vkind, item.vlev, offset, item.vtype);
+        }
+        t.variables.insert(key, item);
+    }
+    pub fn place_parameters(&mut self, names: &[String]) -> i64 {
+        // 实参按顺序压栈，形参依次位于基址之下，返回形参共占的单元数
+        // 值形参占其类型大小的单元，变量形参只占一个单元，存放的是实参的地址
+        // 超出MAX_SIZE时由语义检查报错，这里只保证不溢出
+        let keys: Vec<String> = names.iter().map(|n| self.key(n)).collect();
+        let t: &mut SymbolTable = self.stack.last_mut().unwrap();
+        let sizes: Vec<i64> = keys
+            .iter()
+            .map(|n| match t.variables.get(n) {
+                Some(v) if v.vkind == 1 => v.vtype.size(),
+                _ => 1,
+            })
+            .collect();
+        let n: i64 = sizes.iter().fold(0, |n, &s| n.saturating_add(s));
+        let mut vadr = -n;
+        for ((name, key), size) in names.iter().zip(&keys).zip(sizes) {
+            if let Some(v) = t.variables.get_mut(key) {
+                v.vadr = vadr as i32;
+                if self.trace {
+                    eprintln!("[env] parameter {} (offset {})", name, vadr);
+                }
+            }
+            vadr = vadr.saturating_add(size);
+        }
+        n
+    }
+    pub fn allocate(&mut self, size: i32) -> i32 {
+        // 在当前活动记录中分配不对应变量的隐藏单元，返回其偏移
+        let t: &mut SymbolTable = self.stack.last_mut().unwrap();
+        let offset = t.next;
+        t.next = t.next.saturating_add(size);
+        offset
+    }
+    pub fn frame_fits(&self, size: i64) -> bool {
+        // 当前活动记录能否再分配size个单元
+        (self.frame_size() as i64).checked_add(size).is_some_and(|n| n <= MAX_SIZE)
+    }
+    pub fn frame_size(&self) -> i32 {
+        // 当前活动记录中已分配的单元数（不含实参）
+        self.stack.last().unwrap().next
     }
     pub fn delete_cariable(&mut self, vname: String){
         // 析构一个变量
+        let key = self.key(&vname);
         let t: &mut SymbolTable = self.stack.last_mut().unwrap();
-        t.variables.remove_entry(&vname);
+        t.variables.remove_entry(&key);
     }
-    pub fn add_procedure(&mut self, pname: String) {
+    pub fn add_procedure(&mut self, pname: String, preturn: Option<Type>, ptype: Vec<Type>, pkind: Vec<i32>) {
         // 声明一个过程
+        let key = self.key(&pname);
+        let t: &mut SymbolTable = self.stack.last_mut().unwrap();
+        let item = ProcedureItem::new(pname.clone(), t.get_level(), preturn, ptype, pkind);
+        if self.trace {
+            eprintln!("[env] procedure {} (level {}, parameters {:?} {:?}, returns {:?})", item.pname, item.plev, item.ptype, item.pkind, item.preturn);
+        }
+        t.procedures.insert(key, item);
+    }
+    pub fn add_constant(&mut self, cname: String, ctype: Type, cvalue: i64) {
+        // 声明一个常量
+        let key = self.key(&cname);
         let t: &mut SymbolTable = self.stack.last_mut().unwrap();
-        let item = ProcedureItem::new(pname.clone(), t.get_level());
-        t.procedures.insert(pname, item);
+        let item = ConstantItem::new(cname.clone(), t.get_level(), ctype, cvalue);
+        if self.trace {
+            eprintln!("[env] constant {} (level {}, type {}, value {})", item.cname, item.clev, item.ctype, item.cvalue);
+        }
+        t.constants.insert(key, item);
+    }
+    pub fn add_type(&mut self, tname: String, ttype: Type) {
+        // 声明一个类型
+        let key = self.key(&tname);
+        let t: &mut SymbolTable = self.stack.last_mut().unwrap();
+        let item = TypeItem::new(tname.clone(), t.get_level(), ttype);
+        if self.trace {
+            eprintln!("[env] type {} (level {}, {}, size {})", item.tname, item.tlev, item.ttype, item.ttype.size());
+        }
+        t.types.insert(key, item);
     }
     pub fn delete_procedure(&mut self, pname: String) {
         // 析构一个过程
+        let key = self.key(&pname);
         let t: &mut SymbolTable = self.stack.last_mut().unwrap();
-        t.procedures.remove_entry(&pname);
+        t.procedures.remove_entry(&key);
     }
     pub fn find_symbol(&self, name: String) -> bool{
         // 自顶向下查找一个符号
+        let name = self.key(&name);
         for s in self.stack.iter().rev() {
-            if s.variables.get(&name).is_some()||s.procedures.get(&name).is_some() {
+            if s.variables.contains_key(&name)||s.procedures.contains_key(&name)||s.constants.contains_key(&name)||s.types.contains_key(&name) {
                 return true;
             }
         }
         false
     }
+    pub fn lookup(&self, name: &str) -> Option<Symbol<'_>> {
+        // 自顶向下查找一个符号，返回最内层的声明
+        let name = self.key(name);
+        for s in self.stack.iter().rev() {
+            if let Some(v) = s.variables.get(&name) {
+                return Some(Symbol::Variable(v));
+            }
+            if let Some(p) = s.procedures.get(&name) {
+                return Some(Symbol::Procedure(p));
+            }
+            if let Some(c) = s.constants.get(&name) {
+                return Some(Symbol::Constant(c));
+            }
+            if let Some(t) = s.types.get(&name) {
+                return Some(Symbol::Type(t));
+            }
+        }
+        self.predefined.iter().find(|c| self.key(&c.cname) == name).map(Symbol::Constant)
+    }
+    pub fn constant(&self, name: &str) -> Option<i64> {
+        // 查常量的值，不是常量时返回None，供常量折叠使用
+        match self.lookup(name) {
+            Some(Symbol::Constant(c)) => Some(c.cvalue),
+            _ => None,
+        }
+    }
+    pub fn named_type(&self, name: &str) -> Option<Type> {
+        // 查类型名定义的类型，不是类型时返回None
+        match self.lookup(name) {
+            Some(Symbol::Type(t)) => Some(t.ttype.clone()),
+            _ => None,
+        }
+    }
+    pub fn find_variable_mut(&mut self, name: &str) -> Option<&mut VariableItem> {
+        // 自顶向下查找一个变量，供修改表项
+        let name = self.key(name);
+        self.stack.iter_mut().rev().find_map(|s| s.variables.get_mut(&name))
+    }
+    pub fn set_read_only(&mut self, name: &str, read_only: bool) {
+        // 标记变量是否只读，进入for循环体时标记控制变量，离开时恢复
+        if let Some(v) = self.find_variable_mut(name) {
+            v.vreadonly = read_only;
+            if self.trace {
+                eprintln!("[env] variable {} {}", name, if read_only { "read-only" } else { "writable" });
+            }
+        }
+    }
+    pub fn forward(&self, name: &str) -> Option<&ProcedureItem> {
+        // 查当前作用域中只有前置说明、尚未定义的过程
+        self.stack.last().unwrap().procedures.get(&self.key(name)).filter(|p| p.pstate == 1)
+    }
+    pub fn find_procedure_mut(&mut self, name: &str) -> Option<&mut ProcedureItem> {
+        // 自顶向下查找一个过程，供修改表项
+        let name = self.key(name);
+        self.stack.iter_mut().rev().find_map(|s| s.procedures.get_mut(&name))
+    }
+    pub fn level(&self) -> i32 {
+        // 当前作用域层级
+        self.stack.len() as i32 - 1
+    }
+    pub fn address(&self, name: &str) -> Option<(i32, i32, bool)> {
+        // 求变量的层差、偏移以及是否为变量形参，层差是沿静态链向外的步数
+        // 函数名作为变量使用时，指的是该函数活动记录中的结果单元
+        let level = self.level();
+        match self.lookup(name)? {
+            Symbol::Variable(v) => Some((level - v.vlev, v.vadr, v.vkind == 2)),
+            Symbol::Procedure(p) if p.preturn.is_some() => Some((level - (p.plev + 1), RESULT, false)),
+            _ => None,
+        }
+    }
     pub fn check_repeat(&self, name: String) -> bool {
         // 检查当前作用域是否重复声明某符号
-        let t = self.stack.last().unwrap(); 
-        t.variables.contains_key(&name) || t.procedures.contains_key(&name)
+        let name = self.key(&name);
+        let t = self.stack.last().unwrap();
+        t.variables.contains_key(&name) || t.procedures.contains_key(&name) || t.constants.contains_key(&name) || t.types.contains_key(&name)
     }
     pub fn save(&self){
         // 保存在.var文件
@@ -1,45 +1,82 @@
-use crate::prep::Preprocessor;
-use crate::env::{Token, ErrorMessage};
-// use std::fmt::Error;
-use std::io::{Write};
-use std::fs;
+use crate::env::{Token, ErrorMessage, Diagnostic, Diagnostics};
+use crate::options::Options;
 use std::collections::HashMap;
 
+#[derive(Clone, Copy, PartialEq, Debug, Default)]
+pub struct Span {
+    // 记号在源程序中的位置
+    pub line: usize, // 起始行
+    pub column: usize, // 起始列，按字符计
+    pub start: usize, // 起始字节偏移
+    pub end: usize, // 结束字节偏移
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct SpannedToken {
+    // 带位置信息的记号
+    pub token: Token,
+    pub span: Span,
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct LexError {
+    // 词法错误，指向出错记号的起始位置
+    pub message: ErrorMessage,
+    pub span: Span,
+    pub token: Box<Token>, // 代替出错记号的记号
+}
+
+impl LexError {
+    pub fn token(&self) -> Token {
+        // 代替出错记号交给语法分析的记号
+        *self.token.clone()
+    }
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct Tokens {
+    // 一次分词的结果：完整的token流及其中的词法错误
+    pub tokens: Vec<SpannedToken>,
+    pub diagnostics: Diagnostics,
+}
+
 pub struct Lexer {
-    name: &'static str, // 源程序名
     source: String, // 源程序字符串
     max_len: usize, // 标识符的最大长度
-    mode: &'static str, // 错误的打印模式
 
     // 处于简化考虑，将符号表分为三个部分
     reserve_table: HashMap<&'static str, Token>, // 关键字表
     word_table: HashMap<String, Token>, // 标识符表
     literal_table: HashMap<i64, Token>, // 常量表，存储整型数字常量
+    string_table: HashMap<String, Token>, // 字符串常量表
 
-    // 处于简化考虑，没有设计双缓冲区
+    // 没有设计双缓冲区，而是在UTF-8源串上维护字节游标，每次只解码一个字符
     cha: Option<char>, // 最新读入的字符
-    pos: usize, // cha指针位置
+    pos: usize, // cha的字节偏移
     peek: Option<char>, // 设置缓冲区大小为1
-    nxt: usize, // 缓冲区的指针位置
+    nxt: usize, // peek的字节偏移
 
     token: String, // 已读入的字符串 
-    line: usize, // 已读入的行数
-
-    
-    stream: Vec<Token>, // 已读入的Token流
+    line: usize, // cha所在的行数
+    column: usize, // cha所在的列数，按字符计，制表符推到下一个制表位
+    tab_width: usize, // 制表符的宽度
+    fold: bool, // 关键字是否不区分大小写
+    maxint: i64, // 整数常量的最大值
+    failure: Option<ErrorMessage>, // 当前记号的词法错误
+    finished: bool, // 是否已经给出Eof
+    trace: bool, // 是否在标准错误流打印每个记号
 }
 
 impl Lexer {
-    pub fn new(p: Preprocessor, name: &'static str, mode: &'static str) -> Self {
+    pub fn new(source: String, options: &Options) -> Self {
         let mut l = Lexer {
-            name: name,
-            source: p.content,
+            source,
             max_len: 16,
-            mode: mode,
 
             reserve_table: HashMap::new(),
             word_table: HashMap::new(),
             literal_table: HashMap::new(),
+            string_table: HashMap::new(),
 
             cha: None,
             pos: usize::MAX,
@@ -47,285 +84,97 @@ impl Lexer {
             nxt: 0,
 
             token: String::new(),
-            stream: Vec::new(),
             line: 1,
+            column: 0,
+            tab_width: options.tab_width.max(1),
+            fold: options.language.case_insensitive(),
+            maxint: options.maxint(),
+            failure: None,
+            finished: false,
+            trace: options.trace.lex,
         };
         l.init_reserve();
         l
     }
-    pub fn get_stream(&self) -> Vec<Token> {
-        self.stream.clone()
-    }
-    pub fn analyse(&mut self) {
-        // 分词
-        self.getchar();
-        self.getnbc();
-        
-        loop {
-            self.getnbc();
-            let tk = self.current_token();
-            println!("[new token]:{} {}", self.get_meaning(&tk), self.token);
-            // println!("记录至token流后重置当前token");
-            self.stream.push(tk.clone());
-            self.token.clear();
-            self.peek = None;
-            // println!("--------------------------");
-            if tk == Token::Eof {
-                break;
-            }
-            // println!("当前指向字符{:?}", self.cha);
-        }
-    }
-    pub fn save(&mut self){
-        // 输出为文件
-        let path = format!("{}.dyd", self.name);
-        let mut file = fs::File::create(&path).expect("创建文件失败");
-        for tk in &self.stream {
-            let symbol = self.get_symbol(tk);
-            let id = if self.get_label(tk) < 10 {
-                format!("0{}", self.get_label(tk))
-            } else {
-                format!("{}", self.get_label(tk))
+    pub fn analyse(&mut self) -> Tokens {
+        // 分词，一次读完整个源程序，收集token流和词法错误
+        let mut res = Tokens {
+            tokens: Vec::new(),
+            diagnostics: Vec::new(),
+        };
+        for item in self.by_ref() {
+            let st = match item {
+                Ok(st) => st,
+                Err(e) => {
+                    // 出错的记号交给语法分析处理，越界的整数常量不再重复报错
+                    let token = e.token();
+                    res.diagnostics.push(Diagnostic { message: e.message, span: e.span });
+                    SpannedToken { token, span: e.span }
+                }
             };
-            let line = format!("{:>16} {}\n", symbol, id);
-            file.write_all(line.as_bytes()).expect("写入文件失败");
+            res.tokens.push(st);
         }
+        res
     }
-
-    fn error(&self, errmsg: ErrorMessage) {
-        match self.mode {
-            "console" => self.console_error(errmsg),
-            "file" => self.file_error(&errmsg),
-            _ => println!("invalid mode!"),
-        };
-    }
-    fn file_error(&self, errmsg:&ErrorMessage) {
-        let path = format!("{}.err", self.name);
-        let mut file = fs::File::create(&path).expect("创建错误文件失败");
-        let err_msg = match errmsg {
-            ErrorMessage::SyntaxError => format!("LINE{:?}: unknown token!\n", self.line),
-            ErrorMessage::WrongReserveYouMeanFunction => format!("LINE{:?}: wrong reserve: you mean 'function'?\n", self.line),
-            ErrorMessage::WrongReserveYouMeanRead => format!("LINE{:?}: wrong reserve: you mean 'read'?\n", self.line),
-            ErrorMessage::WrongReserveYouMeanWrite => format!("LINE{:?}: wrong reserve: you mean 'write'?\n", self.line),
-            ErrorMessage::WrongAssignToken => format!("LINE{:?}: wrong assign operator: you mean ':='?\n", self.line),
-            ErrorMessage::InvalidTypeExpectedInterger => format!("LINE{:?}: invalid type: expected INTEGER\n", self.line),
-            ErrorMessage::InvalidNumber => format!("LINE{:?}: Invalid number!\n", self.line),
-            ErrorMessage::OverflowIdentifier => format!("LINE{:?}: Identifier length overflow!\n", self.line),
-            ErrorMessage::FailMatchingSemicolon => format!("LINE{:?}: Semicolon matching failed!\n", self.line),
-            ErrorMessage::MissingSemicolon => format!("LINE{:?}: missing a ';' at the end of the statement\n", self.line),
-            ErrorMessage::MissingLeftParenthesis => format!("LINE{:?}: expected '(' following the function statement\n", self.line),
-            ErrorMessage::MissingRightParenthesis => format!("LINE{:?}: expected ')' to cover the block\n", self.line),
-            ErrorMessage::MissingIf => format!("LINE{:?}: expected 'if' \n", self.line),
-            ErrorMessage::MissingThen => format!("LINE{:?}: expected 'then' \n", self.line),
-            ErrorMessage::MissingElse => format!("LINE{:?}: expected 'else' \n", self.line),
-            ErrorMessage::MissingMultiply => format!("LINE{:?}: expected '*' \n", self.line),
-            ErrorMessage::SyntaxErrorExpectedABlock => format!("LINE{:?}: syntax error, expected a block\n", self.line),
-            ErrorMessage::FailMatching => format!("LINE{:?}: Symbol matching error!\n", self.line),
-            ErrorMessage::MissingEnd => format!("LINE{:?}: missing END: this block is not covered\n", self.line),
-            ErrorMessage::ExpectedIdentifier => format!("LINE{:?}: Expected identifier in this field\n", self.line),
-            ErrorMessage::FoundRepeatDeclarationInThisField => format!("LINE{:?}: this symbol's declaration repeated in this field\n", self.line),
+    fn next_spanned(&mut self) -> Result<SpannedToken, LexError> {
+        // 读出下一个记号及其位置，不记录也不打印
+        if self.pos == usize::MAX {
+            self.getchar();
+        }
+        self.getnbc();
+        let mut span = Span {
+            line: self.line,
+            column: self.column,
+            start: self.pos,
+            end: self.pos,
         };
-        file.write_all(err_msg.as_bytes()).expect("写入错误文件失败");
-    }
-    fn console_error(&self, errmsg: ErrorMessage) {
-        // 抛出错误
-        // 这里还是简化实现
-        // 应该扔到标准错误流中，写入文件
-        // 不能和标准输出流混合
-        match errmsg {
-            ErrorMessage::SyntaxError => {
-                println!("LINE{:?}: 语法错误!", self.line);
-            }
-            ErrorMessage::WrongReserveYouMeanFunction => {
-                println!("LINE{:?}: wrong reserve: you mean 'function'?", self.line);
-            }
-            ErrorMessage::WrongReserveYouMeanRead => {
-                println!("LINE{:?}: wrong reserve: you mean 'read'?", self.line);
-            }
-            ErrorMessage::WrongReserveYouMeanWrite => {
-                println!("LINE{:?}: wrong reserve: you mean 'write'?", self.line);
-            }
-            ErrorMessage::WrongAssignToken => {
-                println!("LINE{:?}: wrong assign operator: you mean ':='?", self.line);
-            }
-            ErrorMessage::InvalidTypeExpectedInterger => {
-                println!("LINE{:?}: 非法的类型,expected integer!", self.line);
-            }
-            ErrorMessage::InvalidNumber => {
-                println!("LINE{:?}: 非法数字!", self.line);
-            }
-            ErrorMessage::OverflowIdentifier => {
-                println!("LINE{:?}: 标识符长度溢出!", self.line);
-            }
-            ErrorMessage::FailMatchingSemicolon => {
-                println!("LINE{:?}: 冒号匹配失败!", self.line);
-            }
-            ErrorMessage::MissingSemicolon => {
-                println!("LINE{:?}: 句尾缺少分号!", self.line);
-            }
-            ErrorMessage::MissingLeftParenthesis => {
-                println!("LINE{:?}: expected '(' following the function statement", self.line);
-            }
-            ErrorMessage::MissingRightParenthesis => {
-                println!("LINE{:?}: expected ')' to cover the block", self.line);
-            }
-            ErrorMessage::MissingThen => {
-                println!("LINE{:?}: expected 'then' ", self.line);
-            }
-            ErrorMessage::MissingIf => {
-                println!("LINE{:?}: expected 'if' ", self.line);
-            }
-            ErrorMessage::MissingElse => {
-                println!("LINE{:?}: expected 'else' ", self.line);
-            }
-            ErrorMessage::MissingMultiply => {
-                println!("LINE{:?}: expected '*' ", self.line);
-            }
-            ErrorMessage::SyntaxErrorExpectedABlock => {
-                println!("LINE{:?}: syntax error, expected a block!", self.line);
-            }
-            ErrorMessage::FailMatching => {
-                println!("LINE{:?}: 符号匹配错误!", self.line);
-            }
-            ErrorMessage::MissingEnd => {
-                println!("LINE{:?}: missing END: this block is not covered", self.line);
-            }
-            ErrorMessage::ExpectedIdentifier => {
-                println!("LINE{:?}: expected indentifier", self.line);
-            }
-            ErrorMessage::FoundRepeatDeclarationInThisField => {
-                println!("LINE{:?}: the declaration of this indentifier repeated in this scope", self.line);
-            }
+        let tk = self.current_token();
+        span.end = self.pos;
+        // println!("记录至token流后重置当前token");
+        self.token.clear();
+        self.peek = None;
+        match self.failure.take() {
+            Some(message) => {
+                // 越界的整数常量以maxint代替，超长的标识符以截断后的拼写代替，其余以首字符记作非法字符
+                let token = match tk {
+                    Token::IntegerLiteral(_) | Token::Identifier(_) => tk,
+                    _ => Token::Illegal(self.source[span.start..].chars().next().unwrap_or('\0')),
+                };
+                Err(LexError { message, span, token: Box::new(token) })
+            }
+            None => Ok(SpannedToken { token: tk, span }),
         }
     }
     fn init_reserve(&mut self) {
         // 初始化关键字表
-        self.reserve_table.insert("integer", Token::Integer);
-        self.reserve_table.insert("function", Token::Function);
-        self.reserve_table.insert("if", Token::If);
-        self.reserve_table.insert("else", Token::Else);
-        self.reserve_table.insert("then", Token::Then);
-        self.reserve_table.insert("read", Token::Read);
-        self.reserve_table.insert("write", Token::Write);
-        self.reserve_table.insert("begin", Token::Begin);
-        self.reserve_table.insert("end", Token::End);
-    }
-    fn get_symbol(&self, tk: &Token) -> String {
-        // 查查token对应的字符串
-        match &tk {
-            Token::Begin => "begin".to_string(),
-            Token::End => "end".to_string(),
-            Token::Integer => "integer".to_string(),
-            Token::If => "if".to_string(),
-            Token::Then => "then".to_string(),
-            Token::Else => "else".to_string(),
-            Token::Function => "function".to_string(),
-            Token::Read => "read".to_string(),
-            Token::Write => "write".to_string(),
-            Token::Identifier(s) => s.clone(),
-            Token::IntegerLiteral(n) => n.to_string(),
-            Token::Equal => "=".to_string(),
-            Token::NotEqual => "<>".to_string(),
-            Token::LessEqual => "<=".to_string(),
-            Token::Less => "<".to_string(),
-            Token::GreaterEqual => ">=".to_string(),
-            Token::Greater => ">".to_string(),
-            Token::Minus => "-".to_string(),
-            Token::Multiply => "*".to_string(),
-            Token::Assign => ":=".to_string(),
-            Token::LeftParenthesis => "(".to_string(),
-            Token::RightParenthesis => ")".to_string(),
-            Token::Semicolon => ";".to_string(),
-            Token::Eol => "\\EOL".to_string(),
-            Token::Eof => "\\EOF".to_string(),
-            Token::Illegal(c) => c.to_string(),
-        }
-    }
-    fn get_label(&self, tk: &Token) ->i32 {
-        // 查Token对应的标号，以供调试使用
-        match &tk {
-            Token::Begin => 1,
-            Token::End => 2,
-            Token::Integer => 3,
-            Token::If => 4,
-            Token::Then => 5,
-            Token::Else => 6,
-            Token::Function => 7,
-            Token::Read => 8,
-            Token::Write => 9,
-            Token::Identifier(_) => 10,
-            Token::IntegerLiteral(_) => 11,
-            Token::Equal => 12,
-            Token::NotEqual => 13,
-            Token::LessEqual => 14,
-            Token::Less => 15,
-            Token::GreaterEqual => 16,
-            Token::Greater => 17,
-            Token::Minus => 18,
-            Token::Multiply => 19,
-            Token::Assign => 20,
-            Token::LeftParenthesis => 21,
-            Token::RightParenthesis => 22,
-            Token::Semicolon =>23,
-            Token::Eol => 24,
-            Token::Eof => 25,
-            _ => 0,
-        }
-    }
-    fn get_meaning(&self, tk: &Token) -> &'static str {
-        // 查token对应的中文含义，以供调试使用
-        match &tk {
-            Token::Integer => "整数类型声明",
-            Token::Function => "函数声明",
-            Token::If => "条件语句开始",
-            Token::Else => "条件语句分支",
-            Token::Then => "条件语句分支",
-            Token::Read => "读取输入",
-            Token::Write => "输出结果",
-            Token::Begin => "程序块开始",
-            Token::End => "程序块结束",
-            Token::LeftParenthesis => "左括号",
-            Token::RightParenthesis => "右括号",
-            Token::Semicolon => "语句结束符",
-            Token::Equal => "等于运算符",
-            Token::Minus => "减法运算符",
-            Token::Multiply => "乘法运算符",
-            Token::LessEqual => "小于等于运算符",
-            Token::GreaterEqual => "大于等于运算符",
-            Token::NotEqual => "不等于运算符",
-            Token::Assign => "赋值运算符",
-            Token::Eol => "换行符",
-            Token::Eof => "文件终止符",
-            Token::Identifier(_) => "标识符",
-            Token::IntegerLiteral(_) => "数字串",
-            _ => "未知token"
+        for (name, tk) in KEYWORDS {
+            self.reserve_table.insert(name, tk.clone());
         }
     }
     fn get_peek(&mut self) {
-        if self.pos < self.source.len() - 1{
-            self.nxt = self.pos + 1;
-        }else {
-            self.nxt = usize::MAX;
-        }
-
-        if self.nxt < self.source.len() {
-            self.peek = self.source.chars().nth(self.nxt);
-        }else {
-            self.peek = None;
-        }
+        // 预读cha之后的一个字符，不移动游标
+        self.nxt = match self.cha {
+            Some(c) => self.pos + c.len_utf8(),
+            None => self.source.len(),
+        };
+        self.peek = self.source[self.nxt..].chars().next();
         // println!("获得peek符为{:?}", self.peek);
     }
+    fn key(&self) -> String {
+        // 查关键字表用的键，不区分大小写时统一为小写
+        match self.fold {
+            true => self.token.to_lowercase(),
+            false => self.token.clone(),
+        }
+    }
     fn reserve(&self) -> Option<Token> {
         // 对token查关键字表,检索到应返回关键字token，没检索到返回None
-        let ident: &String = &self.token;
+        let ident: String = self.key();
         let tk_str: &str = ident.as_str();
-        match self.reserve_table.get(&tk_str) {
-            Some(tk) => Some(tk.clone()),
-            _ => None,
-        }
+        self.reserve_table.get(&tk_str).cloned()
     }
     fn word(&mut self) -> Token{
-        // 查标识符表
+        // 查标识符表，标识符保留原来的拼写，不区分大小写时由符号表统一
         match self.word_table.get(&self.token){
             Some(_) => Token::Identifier(self.token.clone()),
             _ => {
@@ -346,19 +195,22 @@ impl Lexer {
                 tk
             }
         }
-    } 
+    }
+    fn string(&mut self, s: String) -> Token{
+        // 查字符串常量表
+        match self.string_table.get(&s){
+            Some(tk) => tk.clone(),
+            _ => {
+                let tk = Token::StringLiteral(s.clone());
+                self.string_table.insert(s, tk.clone());
+                tk
+            }
+        }
+    }
     fn is_white(&mut self) -> bool{
         // 判断应该跳过的空白符
         match self.cha {
-            Some(c) => {
-                if c == '\n' {
-                    return false
-                }else if c.is_whitespace() {
-                    return true
-                }else {
-                    return false
-                }
-            },
+            Some(c) => c != '\n' && c.is_whitespace(),
             _ => false,
         }
     }
@@ -370,13 +222,8 @@ impl Lexer {
     }
     fn concat(&mut self) {
         // 将cha加入token末尾
-        match self.cha {
-            Some(c) => {
-                self.token.push(c);
-            }
-            _ => {
-                println!("该符号无法拼接token")
-            }
+        if let Some(c) = self.cha {
+            self.token.push(c);
         }
     }
     fn is_lu(&self) -> bool {
@@ -396,95 +243,139 @@ impl Lexer {
     fn is_d(&self) -> bool {
         // 判断是数字
         match self.cha {
-            Some(c) => c.is_digit(10),
+            Some(c) => c.is_ascii_digit(),
             None => false,
         }
     }
-    fn peek_is_a(&self) -> bool {
-        // 判断下一位能否跟在数字后面
-        // 字母不能跟在数字后面
-        match self.peek {
-            Some(c) => c.is_alphabetic(),
-            _ => false,
-        }
-    }
     fn getchar(&mut self) -> Option<char> {
         // 从源程序读入下一个字符
+        // 游标按当前字符的UTF-8宽度前进，读一个字符是O(1)的
+        // 换行符属于它所结束的那一行，离开换行符时行数加一
         if self.pos == usize::MAX {
             self.pos = 0;
         } else {
-            self.pos += 1;
+            match self.cha {
+                Some(c) => {
+                    self.pos += c.len_utf8();
+                    match c {
+                        '\n' => {
+                            self.line += 1;
+                            self.column = 0;
+                        }
+                        '\t' => self.column = self.tab_stop(self.column - 1),
+                        _ => (),
+                    }
+                }
+                None => return None,
+            }
         }
-
-        if self.pos >= self.source.len() {
-            self.cha = None;
-        } else {
-            self.cha = self.source.chars().nth(self.pos);
+        self.cha = self.source[self.pos..].chars().next();
+        if self.cha.is_some() {
+            self.column += 1;
         }
-
         // println!("{:?}", self.cha);
-        if self.cha == Some('\n') {
-            self.line += 1;
-        }
         self.cha
     }
-    fn retract(&mut self) {
-        // 回退一个字符
-        if self.pos > 0 {
-            self.pos -= 1;
-        } else {
-            self.pos = usize::MAX;
-        }
-
-        if self.pos >= self.source.len() {
-            self.cha = None;
-        } else {
-            self.cha = self.source.chars().nth(self.pos);
-        }
-        self.token.pop();
-        // println!("回退到{:?}", self.cha)
+    fn tab_stop(&self, col: usize) -> usize {
+        // 制表符之前已占col列，返回制表符结束的列
+        col / self.tab_width * self.tab_width + self.tab_width
     }
     fn skip_bad_line(&mut self) {
-        // 处理错误，一直读到换行符
-        while self.cha != Some('\n') {
+        // 处理错误，一直读到换行符或文件末尾
+        while self.cha.is_some() && self.cha != Some('\n') {
             self.getchar();
         }
     }
-    fn lex_digits_str(&mut self) -> Token {
-        // 发现需要解析数字串
-        let mut res: i64 = 0;
-        let mut tk: Token = Token::Illegal('\0'); // Initialize with default value
+    fn lex_digits_str(&mut self, radix: u32) -> Token {
+        // 发现需要解析数字串，radix为10或16
+        // 超过maxint后不再累加但继续读完整个数字串，整个数字串作为出错的位置
+        let mut res: Option<i64> = Some(0);
+        while let Some(val) = self.cha.and_then(|c| c.to_digit(radix)) {
+            res = res
+                .and_then(|r| r.checked_mul(radix as i64))
+                .and_then(|r| r.checked_add(val as i64))
+                .filter(|&r| r <= self.maxint);
+            self.concat();
+            self.getchar();
+        }
+        if self.cha.is_some_and(char::is_alphabetic) {
+            // 字母不能跟在数字后面
+            self.failure = Some(ErrorMessage::InvalidNumber);
+            self.skip_bad_line();
+            return Token::Illegal(self.cha.unwrap_or('\0'));
+        }
+        match res {
+            Some(res) => self.literal(res),
+            None => {
+                self.failure = Some(ErrorMessage::LiteralOverflow(self.maxint));
+                Token::IntegerLiteral(self.maxint)
+            }
+        }
+    }
+    fn lex_string(&mut self) -> Token {
+        // 字符串常量，开始的单引号已经拼入token，两个连续的单引号表示一个单引号
+        // 字符串不能跨行，读到行末或文件末尾仍未闭合时报错
+        let mut value = String::new();
         loop {
-            let Some(dig) = self.cha else { break; };
-            match dig.to_digit(10) {
-                Some(val) => {
-                    res = res * 10 + (val as i64);
-                },
+            match self.cha {
+                Some('\'') => {
+                    self.concat();
+                    self.getchar();
+                    if self.cha != Some('\'') {
+                        return self.string(value);
+                    }
+                    self.concat();
+                    self.getchar();
+                    value.push('\'');
+                }
+                Some(c) if c != '\n' => {
+                    self.concat();
+                    self.getchar();
+                    value.push(c);
+                }
                 _ => {
-                    self.retract();
-                    tk = self.literal(res);
-                    self.get_peek();
-                    if self.peek_is_a() {
-                        self.error(ErrorMessage::InvalidNumber);
-                        self.skip_bad_line();
-                        return Token::Illegal(self.cha.unwrap_or('\0'));
+                    self.failure = Some(ErrorMessage::UnterminatedString);
+                    return Token::Illegal('\'');
+                }
+            }
+        }
+    }
+    fn lex_block_comment(&mut self, close: &str) -> Token {
+        // 块注释，开始定界符已经拼入token，一直读到结束定界符
+        // 注释中的换行照常由getchar计入行数
+        let open_len = self.token.len();
+        loop {
+            match self.cha {
+                Some(_) => {
+                    self.concat();
+                    self.getchar();
+                    if self.token.len() >= open_len + close.len() && self.token.ends_with(close) {
+                        return Token::Comment(self.token.clone());
                     }
-                    break;
+                }
+                None => {
+                    // 读到文件末尾仍未闭合，错误的位置即开始定界符的位置
+                    self.failure = Some(ErrorMessage::UnterminatedComment);
+                    return Token::Illegal(self.token.chars().next().unwrap_or('\0'));
                 }
             }
+        }
+    }
+    fn lex_line_comment(&mut self) -> Token {
+        // 行注释，读到行末为止，换行符留给下一个记号
+        while self.cha.is_some() && self.cha != Some('\n') {
             self.concat();
             self.getchar();
         }
-        self.getchar();
-        tk
+        Token::Comment(self.token.clone())
     }
     fn lex_indentifier(&mut self) ->Token {
         // 可能的标识符和关键字
         while !self.is_white() {
             match self.cha {
                 Some(_) if self.is_dlu() => {
-                    if self.token.len() >= self.max_len {
-                        self.error(ErrorMessage::OverflowIdentifier);
+                    if self.token.chars().count() >= self.max_len {
+                        self.failure = Some(ErrorMessage::OverflowIdentifier);
                         self.skip_bad_line();
                         break;
                     }
@@ -518,30 +409,88 @@ impl Lexer {
                 self.getchar();
                 Token::Semicolon
             }
+            Some(',') => {
+                self.concat();
+                self.getchar();
+                Token::Comma
+            }
+            Some('\'') => {
+                self.concat();
+                self.getchar();
+                self.lex_string()
+            }
+            Some('[') => {
+                self.concat();
+                self.getchar();
+                Token::LeftBracket
+            }
+            Some(']') => {
+                self.concat();
+                self.getchar();
+                Token::RightBracket
+            }
+            Some('.') => {
+                self.concat();
+                self.getchar();
+                match self.cha {
+                    Some('.') => {
+                        self.concat();
+                        self.getchar();
+                        Token::Range
+                    }
+                    _ => Token::Dot,
+                }
+            }
             Some('=') => {
                 self.concat();
                 self.getchar();
-                return Token::Equal
+                Token::Equal
             }
             Some('(') => {
+                self.get_peek();
+                self.concat();
+                self.getchar();
+                if self.peek == Some('*') {
+                    // (* ... *) 块注释
+                    self.concat();
+                    self.getchar();
+                    return self.lex_block_comment("*)")
+                }
+                Token::LeftParenthesis
+            }
+            Some('{') => {
+                // { ... } 块注释
+                self.concat();
+                self.getchar();
+                self.lex_block_comment("}")
+            }
+            Some('/') => {
                 self.concat();
                 self.getchar();
-                return Token::LeftParenthesis
+                match self.cha {
+                    Some('/') => self.lex_line_comment(),
+                    _ => Token::Illegal('/'),
+                }
             }
             Some(')') => {
                 self.concat();
                 self.getchar();
-                return Token::RightParenthesis
+                Token::RightParenthesis
+            }
+            Some('+') => {
+                self.concat();
+                self.getchar();
+                Token::Plus
             }
             Some('-') => {
                 self.concat();
                 self.getchar();
-                return Token::Minus
+                Token::Minus
             }
             Some('*') => {
                 self.concat();
                 self.getchar();
-                return Token::Multiply
+                Token::Multiply
             }
             Some('<') => {
                 self.getchar();
@@ -561,6 +510,7 @@ impl Lexer {
                 self.getchar();
                 match self.cha {
                     Some('=') => {
+                        self.getchar();
                         Token::GreaterEqual
                     }
                     _ => Token::Greater
@@ -575,11 +525,7 @@ impl Lexer {
                         self.getchar();
                         Token::Assign
                     }
-                    _ => {
-                        self.error(ErrorMessage::FailMatchingSemicolon);
-                        self.skip_bad_line();
-                        Token::Illegal(self.cha.unwrap_or('\0'))
-                    }
+                    _ => Token::Colon,
                 }
             }
             Some(_) if self.is_lu() => {
@@ -590,14 +536,315 @@ impl Lexer {
             Some(_) if self.is_d() => {
                 // println!("预测为数字串");
                 self.get_peek();
-                self.lex_digits_str()
+                self.lex_digits_str(10)
             }
-            None => {return Token::Eof},
+            Some('$') => {
+                // 十六进制数字串，如$FF
+                self.concat();
+                self.getchar();
+                match self.cha.is_some_and(|c| c.is_ascii_hexdigit()) {
+                    true => self.lex_digits_str(16),
+                    false => {
+                        self.failure = Some(ErrorMessage::InvalidNumber);
+                        Token::Illegal('$')
+                    }
+                }
+            }
+            None => {Token::Eof},
             Some(c) =>{
                 self.getchar();
                 Token::Illegal(c)
             },
         }
     }
-    
-}
\ No newline at end of file
+}
+
+impl Iterator for Lexer {
+    // 按需产生记号，读到Eof之后结束
+    type Item = Result<SpannedToken, LexError>;
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.finished {
+            return None;
+        }
+        let res = self.next_spanned();
+        if let Ok(SpannedToken { token: Token::Eof, .. }) = res {
+            self.finished = true;
+        }
+        if self.trace {
+            match &res {
+                Ok(st) => eprintln!("[lex] LINE{}:{} {} {}", st.span.line, st.span.column, st.token.get_meaning(), st.token.get_symbol()),
+                Err(e) => eprintln!("[lex] LINE{}:{} error: {}", e.span.line, e.span.column, e.message),
+            }
+        }
+        Some(res)
+    }
+}
+
+impl Token {
+    pub fn get_symbol(&self) -> String {
+        // 查查token对应的字符串
+        match self {
+            Token::Begin => "begin".to_string(),
+            Token::End => "end".to_string(),
+            Token::Integer => "integer".to_string(),
+            Token::If => "if".to_string(),
+            Token::Then => "then".to_string(),
+            Token::Else => "else".to_string(),
+            Token::Function => "function".to_string(),
+            Token::Read => "read".to_string(),
+            Token::Write => "write".to_string(),
+            Token::Writeln => "writeln".to_string(),
+            Token::While => "while".to_string(),
+            Token::Do => "do".to_string(),
+            Token::Repeat => "repeat".to_string(),
+            Token::Until => "until".to_string(),
+            Token::Div => "div".to_string(),
+            Token::Mod => "mod".to_string(),
+            Token::Boolean => "boolean".to_string(),
+            Token::True => "true".to_string(),
+            Token::False => "false".to_string(),
+            Token::And => "and".to_string(),
+            Token::Or => "or".to_string(),
+            Token::Not => "not".to_string(),
+            Token::Procedure => "procedure".to_string(),
+            Token::Var => "var".to_string(),
+            Token::Array => "array".to_string(),
+            Token::Of => "of".to_string(),
+            Token::Const => "const".to_string(),
+            Token::Type => "type".to_string(),
+            Token::Record => "record".to_string(),
+            Token::Dot => ".".to_string(),
+            Token::For => "for".to_string(),
+            Token::To => "to".to_string(),
+            Token::Downto => "downto".to_string(),
+            Token::Case => "case".to_string(),
+            Token::Forward => "forward".to_string(),
+            Token::Unit => "unit".to_string(),
+            Token::Interface => "interface".to_string(),
+            Token::Implementation => "implementation".to_string(),
+            Token::Uses => "uses".to_string(),
+            Token::Colon => ":".to_string(),
+            Token::LeftBracket => "[".to_string(),
+            Token::RightBracket => "]".to_string(),
+            Token::Range => "..".to_string(),
+            Token::Identifier(s) => s.clone(),
+            Token::IntegerLiteral(n) => n.to_string(),
+            Token::StringLiteral(s) => format!("'{}'", s.replace('\'', "''")),
+            Token::Equal => "=".to_string(),
+            Token::NotEqual => "<>".to_string(),
+            Token::LessEqual => "<=".to_string(),
+            Token::Less => "<".to_string(),
+            Token::GreaterEqual => ">=".to_string(),
+            Token::Greater => ">".to_string(),
+            Token::Plus => "+".to_string(),
+            Token::Minus => "-".to_string(),
+            Token::Multiply => "*".to_string(),
+            Token::Assign => ":=".to_string(),
+            Token::LeftParenthesis => "(".to_string(),
+            Token::RightParenthesis => ")".to_string(),
+            Token::Semicolon => ";".to_string(),
+            Token::Comma => ",".to_string(),
+            Token::Eol => "\\EOL".to_string(),
+            Token::Eof => "\\EOF".to_string(),
+            Token::Comment(s) => s.clone(),
+            Token::Illegal(c) => c.to_string(),
+        }
+    }
+    pub fn get_label(&self) ->i32 {
+        // 查Token对应的标号，以供调试使用
+        match self {
+            Token::Begin => 1,
+            Token::End => 2,
+            Token::Integer => 3,
+            Token::If => 4,
+            Token::Then => 5,
+            Token::Else => 6,
+            Token::Function => 7,
+            Token::Read => 8,
+            Token::Write => 9,
+            Token::Identifier(_) => 10,
+            Token::IntegerLiteral(_) => 11,
+            Token::Equal => 12,
+            Token::NotEqual => 13,
+            Token::LessEqual => 14,
+            Token::Less => 15,
+            Token::GreaterEqual => 16,
+            Token::Greater => 17,
+            Token::Minus => 18,
+            Token::Multiply => 19,
+            Token::Assign => 20,
+            Token::LeftParenthesis => 21,
+            Token::RightParenthesis => 22,
+            Token::Semicolon =>23,
+            Token::Eol => 24,
+            Token::Eof => 25,
+            Token::While => 26,
+            Token::Do => 27,
+            Token::Repeat => 28,
+            Token::Until => 29,
+            Token::Plus => 30,
+            Token::Div => 31,
+            Token::Mod => 32,
+            Token::Boolean => 33,
+            Token::True => 34,
+            Token::False => 35,
+            Token::And => 36,
+            Token::Or => 37,
+            Token::Not => 38,
+            Token::Comma => 39,
+            Token::Procedure => 40,
+            Token::Var => 41,
+            Token::Array => 42,
+            Token::Of => 43,
+            Token::LeftBracket => 44,
+            Token::RightBracket => 45,
+            Token::Range => 46,
+            Token::StringLiteral(_) => 47,
+            Token::Writeln => 48,
+            Token::Const => 49,
+            Token::Type => 50,
+            Token::Record => 51,
+            Token::Dot => 52,
+            Token::For => 53,
+            Token::To => 54,
+            Token::Downto => 55,
+            Token::Case => 56,
+            Token::Colon => 57,
+            Token::Forward => 58,
+            Token::Unit => 59,
+            Token::Interface => 60,
+            Token::Implementation => 61,
+            Token::Uses => 62,
+            _ => 0,
+        }
+    }
+    pub fn get_meaning(&self) -> &'static str {
+        // 查token对应的中文含义，以供调试使用
+        match self {
+            Token::Integer => "整数类型声明",
+            Token::Function => "函数声明",
+            Token::If => "条件语句开始",
+            Token::Else => "条件语句分支",
+            Token::Then => "条件语句分支",
+            Token::Read => "读取输入",
+            Token::Write => "输出结果",
+            Token::Writeln => "输出结果并换行",
+            Token::While => "当型循环开始",
+            Token::Do => "循环体开始",
+            Token::Repeat => "直到型循环开始",
+            Token::Until => "循环条件",
+            Token::Begin => "程序块开始",
+            Token::End => "程序块结束",
+            Token::LeftParenthesis => "左括号",
+            Token::RightParenthesis => "右括号",
+            Token::Semicolon => "语句结束符",
+            Token::Comma => "逗号",
+            Token::Equal => "等于运算符",
+            Token::Plus => "加法运算符",
+            Token::Minus => "减法运算符",
+            Token::Div => "整除运算符",
+            Token::Mod => "取余运算符",
+            Token::Boolean => "布尔类型声明",
+            Token::True => "布尔常量",
+            Token::False => "布尔常量",
+            Token::And => "逻辑与运算符",
+            Token::Or => "逻辑或运算符",
+            Token::Not => "逻辑非运算符",
+            Token::Procedure => "过程声明",
+            Token::Var => "变量形参",
+            Token::Array => "数组类型",
+            Token::Of => "元素类型",
+            Token::Const => "常量说明",
+            Token::Type => "类型说明",
+            Token::Record => "记录类型",
+            Token::Dot => "域选择符",
+            Token::For => "计数循环开始",
+            Token::To => "递增计数",
+            Token::Downto => "递减计数",
+            Token::Case => "情况语句开始",
+            Token::Colon => "冒号",
+            Token::Forward => "前置说明",
+            Token::Unit => "单元开始",
+            Token::Interface => "接口部分",
+            Token::Implementation => "实现部分",
+            Token::Uses => "引用单元",
+            Token::LeftBracket => "左方括号",
+            Token::RightBracket => "右方括号",
+            Token::Range => "子界符",
+            Token::Multiply => "乘法运算符",
+            Token::LessEqual => "小于等于运算符",
+            Token::GreaterEqual => "大于等于运算符",
+            Token::NotEqual => "不等于运算符",
+            Token::Assign => "赋值运算符",
+            Token::Eol => "换行符",
+            Token::Eof => "文件终止符",
+            Token::Identifier(_) => "标识符",
+            Token::IntegerLiteral(_) => "数字串",
+            Token::StringLiteral(_) => "字符串",
+            Token::Comment(_) => "注释",
+            _ => "未知token"
+        }
+    }
+}
+
+pub const KEYWORDS: &[(&str, Token)] = &[
+    // 全部关键字及其记号，也供拼写建议使用
+    ("integer", Token::Integer),
+    ("function", Token::Function),
+    ("if", Token::If),
+    ("else", Token::Else),
+    ("then", Token::Then),
+    ("read", Token::Read),
+    ("write", Token::Write),
+    ("writeln", Token::Writeln),
+    ("begin", Token::Begin),
+    ("end", Token::End),
+    ("while", Token::While),
+    ("do", Token::Do),
+    ("repeat", Token::Repeat),
+    ("until", Token::Until),
+    ("div", Token::Div),
+    ("mod", Token::Mod),
+    ("boolean", Token::Boolean),
+    ("true", Token::True),
+    ("false", Token::False),
+    ("and", Token::And),
+    ("or", Token::Or),
+    ("not", Token::Not),
+    ("procedure", Token::Procedure),
+    ("var", Token::Var),
+    ("array", Token::Array),
+    ("of", Token::Of),
+    ("const", Token::Const),
+    ("type", Token::Type),
+    ("record", Token::Record),
+    ("for", Token::For),
+    ("to", Token::To),
+    ("downto", Token::Downto),
+    ("case", Token::Case),
+    ("forward", Token::Forward),
+    ("unit", Token::Unit),
+    ("interface", Token::Interface),
+    ("implementation", Token::Implementation),
+    ("uses", Token::Uses),
+];
+
+pub fn dyd(tokens: &[SpannedToken]) -> String {
+    // 生成二元式文件的内容
+    let mut out = String::new();
+    for st in tokens {
+        let tk = &st.token;
+        if let Token::Comment(_) = tk {
+            // 注释只作为附加信息保留在token流中，不写入二元式文件
+            continue;
+        }
+        let symbol = tk.get_symbol();
+        let id = if tk.get_label() < 10 {
+            format!("0{}", tk.get_label())
+        } else {
+            format!("{}", tk.get_label())
+        };
+        out.push_str(&format!("{:>16} {}\n", symbol, id));
+    }
+    out
+}
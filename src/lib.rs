@@ -0,0 +1,113 @@
+pub mod options;
+pub mod types;
+pub mod prep;
+pub mod lex;
+pub mod env;
+pub mod ast;
+pub mod parse;
+pub mod check;
+pub mod code;
+pub mod vm;
+pub mod unit;
+pub mod suggest;
+
+// 编译器各阶段的纯函数接口
+// 只处理内存中的数据，不读写文件，也不向标准输出打印，读写由调用者负责
+// 打开Options中的跟踪开关后，各阶段把跟踪信息写到标准错误流
+
+pub use ast::Ast;
+pub use env::{Diagnostic, Diagnostics};
+pub use code::Program;
+pub use lex::Tokens;
+pub use options::{Language, Options, Trace};
+pub use prep::{decode, load, Decoded, Include, Preprocessed, SourceMap};
+pub use unit::{fingerprint, Interface, Resolver};
+
+use check::Checker;
+use code::Generator;
+use vm::Machine;
+use std::io::{BufRead, Write};
+use lex::Lexer;
+use parse::Parser;
+use prep::Preprocessor;
+
+pub fn preprocess(source: &str) -> (String, Diagnostics) {
+    // 预处理，不能包含其他文件，预处理错误随结果一并返回
+    let res = preprocess_with(source.as_bytes(), "", &Options::default(), &|_, _| None);
+    (res.content, res.diagnostics)
+}
+
+pub fn preprocess_with(source: &[u8], file: &str, options: &Options, include: Include) -> Preprocessed {
+    // source是源文件的原始字节，file是源程序的文件名，include由文件名和包含它的文件求出被包含文件的路径和原始字节
+    Preprocessor::new(options, include).run(file, source)
+}
+
+pub fn lex(source: &str) -> Tokens {
+    // 词法分析，词法错误记录在结果中
+    lex_with(source, &Options::default())
+}
+
+pub fn lex_with(source: &str, options: &Options) -> Tokens {
+    Lexer::new(source.to_string(), options).analyse()
+}
+
+pub fn parse(tokens: Tokens) -> Result<Ast, Diagnostics> {
+    // 语法分析，出错时返回语法错误
+    parse_with(tokens, &Options::default())
+}
+
+pub fn parse_with(tokens: Tokens, options: &Options) -> Result<Ast, Diagnostics> {
+    let mut parser = Parser::new(tokens.tokens.into_iter().map(Ok), options);
+    match parser.analyse() {
+        Ok(ast) => Ok(ast),
+        Err(_) => Err(parser.take_diagnostics()),
+    }
+}
+
+pub fn check(ast: &Ast) -> Diagnostics {
+    // 语义检查
+    check_with(ast, &Options::default())
+}
+
+pub fn check_with(ast: &Ast, options: &Options) -> Diagnostics {
+    Checker::new(options).analyse(ast, &[])
+}
+
+pub fn check_with_units(ast: &Ast, units: &[Interface], options: &Options) -> Diagnostics {
+    // 引用了单元的程序或单元，units中是模块解析得到的单元
+    Checker::new(options).analyse(ast, units)
+}
+
+pub fn generate(ast: &Ast) -> Program {
+    // 代码生成，只接受通过语义检查的语法树
+    generate_with(ast, &Options::default())
+}
+
+pub fn generate_with(ast: &Ast, options: &Options) -> Program {
+    Generator::new(options).generate(ast, &[])
+}
+
+pub fn generate_with_units(ast: &Ast, units: &[Interface], options: &Options) -> Program {
+    // 生成的代码中对单元中过程的调用要经过连接才能执行
+    Generator::new(options).generate(ast, units)
+}
+
+pub fn generate_unit(ast: &Ast, units: &[Interface], options: &Options) -> Interface {
+    // 编译单元，得到写入接口文件的内容
+    Generator::new(options).generate_unit(ast, units)
+}
+
+pub fn link(program: Program, units: &[Interface]) -> Result<Program, Diagnostic> {
+    // 连接，把程序引用的各单元的代码接在程序之后
+    unit::link(program, units)
+}
+
+pub fn run(program: &Program, input: &mut dyn BufRead, output: &mut dyn Write) -> Result<(), Diagnostic> {
+    // 解释执行目标代码，read从input读入，write写到output，运行时错误作为报错信息返回
+    run_with(program, &Options::default(), input, output)
+}
+
+pub fn run_with(program: &Program, options: &Options, input: &mut dyn BufRead, output: &mut dyn Write) -> Result<(), Diagnostic> {
+    // 按options中integer的位数检查运算结果是否溢出
+    Machine::new(program, options.maxint(), input, output).run()
+}
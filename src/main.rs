@@ -1,30 +1,224 @@
-pub mod prep;
-pub mod lex;
-pub mod env;
-pub mod parse;
+use std::env;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process;
 
-use prep::Preprocessor;
-use lex::Lexer;
-use parse::Parser;
-use env::Env;
+use compiler::{Language, fingerprint, preprocess_with, load, lex_with, parse_with, check_with_units, generate_with_units, generate_unit, link, run_with, Options, Resolver, Trace};
+
+const USAGE: &str = "usage: compiler [-v | -vv] [--trace lex,parse,env] [--mode console|file] [--run] [--short-circuit] [--no-bounds-check] [-I <dir>]... [-D <symbol>]... [--tab-width <n>] [--language course|pascal] [--int-width 16|32|64] <source>[.pas]";
+
+struct Args {
+    // 命令行参数
+    path: String, // 源程序名，不含.pas后缀
+    mode: String, // 错误的打印模式
+    verbose: u8, // -v 打印各阶段概要，-vv 同时打开全部跟踪
+    trace: Trace, // --trace 指定的跟踪阶段
+    run: bool, // 编译通过后是否解释执行
+    short_circuit: bool, // and/or是否短路求值
+    bounds_check: bool, // 是否检查数组下标越界
+    include: Vec<String>, // -I 指定的单元和包含文件的搜索路径
+    defines: Vec<String>, // -D 定义的条件编译符号
+    tab_width: usize, // 计算列数时制表符的宽度
+    language: Language, // 语言模式
+    int_width: u32, // integer的位数
+}
+
+fn parse_args() -> Result<Args, String> {
+    let mut args = Args {
+        path: String::new(),
+        mode: "file".to_string(),
+        verbose: 0,
+        trace: Trace::default(),
+        run: false,
+        short_circuit: false,
+        bounds_check: true,
+        include: Vec::new(),
+        defines: Vec::new(),
+        tab_width: Options::default().tab_width,
+        language: Language::Course,
+        int_width: Options::default().int_width,
+    };
+    let mut it = env::args().skip(1);
+    while let Some(arg) = it.next() {
+        match arg.as_str() {
+            "-v" => args.verbose += 1,
+            "-vv" => args.verbose += 2,
+            "--trace" => {
+                let list = it.next().ok_or("--trace expects a list of phases")?;
+                args.trace = args.trace.merge(Trace::parse_list(&list)?);
+            }
+            "--mode" => {
+                args.mode = it.next().ok_or("--mode expects console or file")?;
+            }
+            "--run" => args.run = true,
+            "--short-circuit" => args.short_circuit = true,
+            "--no-bounds-check" => args.bounds_check = false,
+            "-I" => args.include.push(it.next().ok_or("-I expects a directory")?),
+            "--tab-width" => {
+                let width = it.next().ok_or("--tab-width expects a number")?;
+                args.tab_width = width.parse().ok().filter(|&w| w > 0).ok_or(format!("invalid tab width '{}'", width))?;
+            }
+            "--language" => {
+                let name = it.next().ok_or("--language expects course or pascal")?;
+                args.language = Language::parse(&name)?;
+            }
+            "--int-width" => {
+                let width = it.next().ok_or("--int-width expects 16, 32 or 64")?;
+                args.int_width = width.parse().ok().filter(|w| [16, 32, 64].contains(w)).ok_or(format!("invalid integer width '{}'", width))?;
+            }
+            "-D" => args.defines.push(it.next().ok_or("-D expects a symbol")?),
+            _ if arg.starts_with("-D") => args.defines.push(arg[2..].to_string()),
+            "-h" | "--help" => return Err(String::new()),
+            _ if arg.starts_with('-') => return Err(format!("unknown option '{}'", arg)),
+            _ => args.path = arg.trim_end_matches(".pas").to_string(),
+        }
+    }
+    if args.path.is_empty() {
+        return Err("missing source file".to_string());
+    }
+    if args.verbose >= 2 {
+        args.trace = Trace::all();
+    }
+    Ok(args)
+}
+
+fn report(path: &str, mode: &str, diagnostics: &[String]) {
+    // 输出按源映射定位好的报错信息：console模式写到标准错误流，file模式写入.err文件
+    match mode {
+        "console" => {
+            for d in diagnostics {
+                eprintln!("{}", d);
+            }
+        }
+        "file" => {
+            let mut out = String::new();
+            for d in diagnostics {
+                out.push_str(&format!("{}\n", d));
+            }
+            fs::write(format!("{}.err", path), out).expect("写入错误文件失败");
+        }
+        _ => eprintln!("invalid mode!"),
+    }
+}
 
 fn main() {
-    let path = "test/6";
-    let mode = "file";
-    let preprocessor = Preprocessor::new(path);
-    println!("---------------------------------");
-
-    let mut lexer = Lexer::new(preprocessor, path, mode);
-    lexer.analyse();
-    lexer.save();
-    println!("---------------------------------");
-
-    let mut env = Env::new();
-    let s = lexer.get_stream();
-    
-    let mut parser = Parser::new(s, mode, path.to_string());
-    match parser.analyse(&mut env) {
-        Ok(()) => println!("compilered!"),
-        _ => println!("syntax error!")
+    let args = match parse_args() {
+        Ok(args) => args,
+        Err(e) => {
+            if !e.is_empty() {
+                eprintln!("{}", e);
+            }
+            eprintln!("{}", USAGE);
+            process::exit(2);
+        }
+    };
+    let options = Options {
+        trace: args.trace,
+        short_circuit: args.short_circuit,
+        bounds_check: args.bounds_check,
+        defines: args.defines.clone(),
+        tab_width: args.tab_width,
+        language: args.language,
+        int_width: args.int_width,
+    };
+    let path = args.path.as_str();
+    let verbose = |msg: String| {
+        if args.verbose >= 1 {
+            eprintln!("[compiler] {}", msg);
+        }
+    };
+
+    let source = match fs::read(format!("{}.pas", path)) {
+        Ok(s) => s,
+        Err(e) => {
+            eprintln!("{}.pas: {}", path, e);
+            process::exit(1);
+        }
+    };
+    verbose(format!("read {}.pas, {} bytes", path, source.len()));
+
+    // 包含的文件和引用的单元都先在源程序所在的目录，再在-I指定的目录中查找
+    // 被包含的文件还要先在包含它的文件所在的目录中查找
+    let dir = Path::new(path).parent().filter(|d| !d.as_os_str().is_empty()).unwrap_or(Path::new("."));
+    let paths: Vec<PathBuf> = std::iter::once(dir.to_path_buf()).chain(args.include.iter().map(PathBuf::from)).collect();
+    let prep = preprocess_with(&source, &format!("{}.pas", path), &options, &|n, f| load(&paths, n, f));
+    verbose(format!("preprocess: {}, {} files, {} errors", prep.encoding, prep.map.files.len(), prep.diagnostics.len()));
+    let tokens = lex_with(&prep.content, &options);
+    verbose(format!("lex: {} tokens, {} errors", tokens.tokens.len(), tokens.diagnostics.len()));
+    fs::write(format!("{}.dyd", path), compiler::lex::dyd(&tokens.tokens)).expect("创建文件失败");
+
+    let mut diagnostics = prep.diagnostics.clone();
+    diagnostics.extend(tokens.diagnostics.clone());
+    let mut program = None;
+    match parse_with(tokens, &options) {
+        Ok(ast) => {
+            verbose("parse: ok".to_string());
+            // 编译出错的单元，报错信息写到该单元自己的报错文件中
+            let mut resolver = Resolver::new(paths.clone(), &options);
+            let mut errors = resolver.resolve(ast.unit.as_ref().map(|u| u.name.as_str()), &ast.uses);
+            for (source, unit_errors) in &resolver.failures {
+                if args.mode == "console" {
+                    eprintln!("{}:", source.display());
+                }
+                report(&source.with_extension("").to_string_lossy(), &args.mode, unit_errors);
+            }
+            verbose(format!("resolve: {} units", resolver.units().len()));
+            if errors.is_empty() {
+                errors = check_with_units(&ast, resolver.units(), &options);
+                verbose(format!("check: {} errors", errors.len()));
+            }
+            if errors.is_empty() && diagnostics.is_empty() {
+                match &ast.unit {
+                    // 单元写出接口文件，供引用它的程序使用
+                    Some(_) => {
+                        let mut unit = generate_unit(&ast, resolver.units(), &options);
+                        unit.source = fingerprint(&source);
+                        verbose(format!("generate: {} instructions", unit.program.code.len()));
+                        fs::write(format!("{}.itf", path), unit.to_string()).expect("写入接口文件失败");
+                    }
+                    None => {
+                        let code = generate_with_units(&ast, resolver.units(), &options);
+                        verbose(format!("generate: {} instructions", code.code.len()));
+                        match link(code, resolver.units()) {
+                            Ok(code) => program = Some(code),
+                            Err(e) => errors.push(e),
+                        }
+                    }
+                }
+            }
+            diagnostics.extend(errors);
+        }
+        Err(errors) => {
+            verbose(format!("parse: {} errors", errors.len()));
+            diagnostics.extend(errors);
+        }
+    }
+    let rendered: Vec<String> = diagnostics.iter().map(|d| prep.map.render(d)).collect();
+    report(path, &args.mode, &rendered);
+    match diagnostics.is_empty() {
+        true => println!("compilered!"),
+        false => println!("syntax error!"),
+    }
+
+    // 解释执行，运行时错误与编译错误一样报告
+    // console模式读标准输入、写标准输出；file模式有<name>.in时从中读入，程序的输出写入<name>.out
+    if let (true, Some(program)) = (args.run, program) {
+        let result = match args.mode.as_str() {
+            "file" => {
+                let mut output = Vec::new();
+                let result = match fs::read(format!("{}.in", path)) {
+                    Ok(input) => run_with(&program, &options, &mut input.as_slice(), &mut output),
+                    Err(_) => run_with(&program, &options, &mut io::stdin().lock(), &mut output),
+                };
+                fs::write(format!("{}.out", path), output).expect("写入输出文件失败");
+                result
+            }
+            _ => run_with(&program, &options, &mut io::stdin().lock(), &mut io::stdout().lock()),
+        };
+        if let Err(e) = result {
+            report(path, &args.mode, &[prep.map.render(&e)]);
+            process::exit(1);
+        }
     }
-}
\ No newline at end of file
+}
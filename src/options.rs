@@ -0,0 +1,94 @@
+// 编译选项，由调用者构造后传给各个阶段
+// 各阶段只读取自己关心的字段
+
+#[derive(Clone, Copy, PartialEq, Debug, Default)]
+pub struct Trace {
+    // 各阶段的跟踪输出开关，跟踪信息写到标准错误流
+    pub lex: bool, // 打印每个记号
+    pub parse: bool, // 打印每个parse_node_*的进入与退出
+    pub env: bool, // 打印作用域变化和符号声明
+}
+
+impl Trace {
+    pub fn all() -> Self {
+        Trace {
+            lex: true,
+            parse: true,
+            env: true,
+        }
+    }
+    pub fn parse_list(list: &str) -> Result<Self, String> {
+        // 解析形如 lex,parse,env 的阶段列表
+        let mut t = Trace::default();
+        for phase in list.split(',').map(str::trim).filter(|p| !p.is_empty()) {
+            match phase {
+                "lex" => t.lex = true,
+                "parse" => t.parse = true,
+                "env" => t.env = true,
+                "all" => t = Trace::all(),
+                _ => return Err(format!("unknown trace phase '{}'", phase)),
+            }
+        }
+        Ok(t)
+    }
+    pub fn merge(self, other: Trace) -> Self {
+        Trace {
+            lex: self.lex || other.lex,
+            parse: self.parse || other.parse,
+            env: self.env || other.env,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug, Default)]
+pub enum Language {
+    // 语言模式
+    #[default]
+    Course, // 课程实验的语言，关键字和标识符区分大小写
+    Pascal, // 按标准Pascal，关键字和标识符不区分大小写
+}
+
+impl Language {
+    pub fn parse(name: &str) -> Result<Self, String> {
+        match name {
+            "course" => Ok(Language::Course),
+            "pascal" => Ok(Language::Pascal),
+            _ => Err(format!("unknown language '{}'", name)),
+        }
+    }
+    pub fn case_insensitive(self) -> bool {
+        self == Language::Pascal
+    }
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct Options {
+    pub trace: Trace, // 跟踪输出，默认全部关闭
+    pub short_circuit: bool, // and/or是否短路求值，默认按标准Pascal对两个操作数都求值
+    pub bounds_check: bool, // 运行时是否检查数组下标和子界越界，默认检查
+    pub defines: Vec<String>, // 预处理前已定义的符号，供{$IFDEF}使用
+    pub tab_width: usize, // 计算列数时制表符的宽度，默认4
+    pub language: Language, // 语言模式，默认为课程实验的语言
+    pub int_width: u32, // integer的位数，16、32或64，默认64
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Options {
+            trace: Trace::default(),
+            short_circuit: false,
+            bounds_check: true,
+            defines: Vec::new(),
+            tab_width: 4,
+            language: Language::Course,
+            int_width: 64,
+        }
+    }
+}
+
+impl Options {
+    pub fn maxint(&self) -> i64 {
+        // integer的最大值，最小值是-maxint-1
+        ((1i128 << (self.int_width.clamp(2, 64) - 1)) - 1) as i64
+    }
+}
@@ -1,629 +1,1170 @@
-use crate::env::{Token, ErrorMessage, Env};
-use std::fs;
-use std::io::{Write};
+use crate::ast::{Ast, Block, CaseArm, CaseLabel, Declaration, Field, Parameter, Statement, Expression, Operator, TypeSpec, Unit};
+use crate::env::{Token, ErrorMessage, Diagnostic, Diagnostics};
+use crate::lex::{KEYWORDS, LexError, SpannedToken, Span};
+use crate::options::Options;
+use crate::suggest::{suggest, with_suggestion};
+use std::collections::VecDeque;
 
-pub struct Parser {
+macro_rules! traced {
+    // 跟踪一个parse_node_*的进入与退出，函数体在闭包中执行以便捕获所有返回路径
+    ($self:ident, $name:expr, $body:block) => {{
+        $self.enter($name);
+        #[allow(clippy::redundant_closure_call)]
+        let res = (|| $body)();
+        $self.exit($name, res.is_ok());
+        res
+    }};
+}
+
+pub struct Parser<I: Iterator<Item = Result<SpannedToken, LexError>>> {
     // LL1语法分析器,基于递归下降办法
-    pub stream: Vec<Token>, // 输入的token流
+    tokens: I, // 输入的token流，按需拉取
+    lookahead: VecDeque<SpannedToken>, // 预读缓冲区，只保存有限个记号
+    previous: Token, // 上一个记号，出错时用于拼写建议
     pub pos: usize, //当前token所在位置
     pub line: usize, // 当前token所在行数
-    mode: &'static str, // 错误的打印模式
-    name: String,
+    diagnostics: Diagnostics, // 收集到的报错信息
+    trace: bool, // 是否在标准错误流打印分析过程
+    depth: usize, // 跟踪输出的缩进层次
+    interface: bool, // 是否在单元的接口部分，其中的过程只有首部
+    declared: Vec<String>, // 已说明的名字，不分作用域，出错时用于判断语句开头的标识符是否写错的关键字
+    fold: bool, // 名字是否不区分大小写
 }
 
-impl Parser {
-    pub fn new(s: Vec<Token>, mode: &'static str, name: String) -> Self {
-        let p = Parser {
-            stream: s,
+impl<I: Iterator<Item = Result<SpannedToken, LexError>>> Parser<I> {
+    pub fn new(tokens: I, options: &Options) -> Self {
+        Parser {
+            tokens,
+            lookahead: VecDeque::new(),
+            previous: Token::Eof,
             pos: 0,
             line: 1,
-            mode: mode,
-            name: name,
-        };
-        p
+            diagnostics: Vec::new(),
+            trace: options.trace.parse,
+            depth: 0,
+            interface: false,
+            declared: Vec::new(),
+            fold: options.language.case_insensitive(),
+        }
     }
-    pub fn analyse(&mut self, env: &mut Env) -> Result<(), ErrorMessage> {
-        self.parse_node_program(env)
+    pub fn analyse(&mut self) -> Result<Ast, ErrorMessage> {
+        self.fill(1);
+        self.parse_node_program()
     }
-
-    fn debug(&self) {
-        println!("-------------------------");
-        println!("debug point");
-        println!("pos:{:?}", self.pos);
-        println!("line:{:?}", self.line);
-        println!("token:{:?}", self.current_token());
+    pub fn take_diagnostics(&mut self) -> Diagnostics {
+        std::mem::take(&mut self.diagnostics)
     }
+
     fn current_token(&self) -> Token {
-        if self.pos >= self.stream.len() {
-            Token::Eof
-        } else {
-            self.stream[self.pos].clone()
+        match self.lookahead.front() {
+            Some(st) => st.token.clone(),
+            None => Token::Eof,
+        }
+    }
+    fn current_span(&self) -> Span {
+        match self.lookahead.front() {
+            Some(st) => st.span,
+            None => Span { line: self.line, ..Span::default() },
         }
     }
     fn advance(&mut self) {
-        if self.pos >= self.stream.len() {
+        if self.match_token(Token::Eof) {
             return;
         }
-        
+        if let Some(st) = self.lookahead.pop_front() {
+            self.previous = st.token;
+        }
         self.pos += 1;
-        let mut tk = self.current_token();
-        
-        // 处理换行符
-        while tk == Token::Eol && self.pos < self.stream.len() {
-            self.line += 1;
-            self.pos += 1;
-            tk = self.current_token();
+        self.fill(1);
+    }
+    fn fill(&mut self, n: usize) {
+        // 从token流拉取记号，直到预读缓冲区中有n个记号
+        // 换行符和注释只是附加信息，在这里跳过
+        while self.lookahead.len() < n {
+            let st = match self.tokens.next() {
+                Some(Ok(st)) => st,
+                Some(Err(e)) => {
+                    // 词法错误：在出错位置报告，并以词法分析给出的记号代替，交给语法分析处理
+                    let token = e.token();
+                    self.diagnostics.push(Diagnostic { message: e.message, span: e.span });
+                    SpannedToken { token, span: e.span }
+                }
+                None => break,
+            };
+            match st.token {
+                Token::Eol | Token::Comment(_) => continue,
+                _ => self.lookahead.push_back(st),
+            }
+        }
+        // 行数取当前记号的起始行
+        if let Some(st) = self.lookahead.front() {
+            self.line = st.span.line;
+        }
+    }
+    fn peek_token(&mut self, n: usize) -> Token {
+        // 预读当前记号之后的第n个记号，用于区分以标识符开头的语句
+        self.fill(n + 1);
+        match self.lookahead.get(n) {
+            Some(st) => st.token.clone(),
+            None => Token::Eof,
         }
     }
     fn match_token(&self, tk: Token) -> bool {
         self.current_token() == tk
     }
-    fn error(&self, errmsg: ErrorMessage) {
-        match self.mode {
-            "console" => self.console_error(errmsg),
-            "file" => self.file_error(&errmsg),
-            _ => println!("invalid mode!"),
+    fn handle_error<T>(&mut self, errmsg: ErrorMessage) -> Result<T, ErrorMessage>{
+        // 在当前记号处记录错误，并放弃分析
+        // 错误只在发现处记录一次，上层直接传递
+        // 当前记号或上一个记号是与某个关键字拼写相近的标识符时，很可能是写错的关键字，附上建议
+        // 自带提示的错误不再附加建议
+        let suggestion = match errmsg {
+            ErrorMessage::WrongAssignToken | ErrorMessage::DidYouMean(..) => None,
+            _ => [self.current_token(), self.previous.clone()].into_iter().find_map(|tk| match tk {
+                Token::Identifier(name) => suggest(&name, KEYWORDS.iter().map(|(k, _)| *k)),
+                _ => None,
+            }),
         };
+        let errmsg = with_suggestion(errmsg, suggestion);
+        if self.trace {
+            eprintln!("[parse] {:indent$}! LINE{}: {}", "", self.line, errmsg, indent = self.depth * 2);
+        }
+        self.diagnostics.push(Diagnostic { message: errmsg.clone(), span: self.current_span() });
+        Err(errmsg)
     }
-    fn file_error(&self, errmsg:&ErrorMessage) {
-        let path = format!("{}.err", self.name);
-        let mut file = fs::OpenOptions::new()
-            .write(true)
-            .append(true)
-            .create(true)
-            .open(&path)
-            .expect("Failed to create error file");
-        let err_msg = match errmsg {
-            ErrorMessage::SyntaxError => format!("LINE{:?}: unknown token!\n", self.line),
-            ErrorMessage::WrongReserveYouMeanFunction => format!("LINE{:?}: wrong reserve: you mean 'function'?\n", self.line),
-            ErrorMessage::WrongReserveYouMeanRead => format!("LINE{:?}: wrong reserve: you mean 'read'?\n", self.line),
-            ErrorMessage::WrongReserveYouMeanWrite => format!("LINE{:?}: wrong reserve: you mean 'write'?\n", self.line),
-            ErrorMessage::WrongAssignToken => format!("LINE{:?}: wrong assign operator: you mean ':='?\n", self.line),
-            ErrorMessage::InvalidTypeExpectedInterger => format!("LINE{:?}: invalid type: expected INTEGER\n", self.line),
-            ErrorMessage::InvalidNumber => format!("LINE{:?}: Invalid number!\n", self.line),
-            ErrorMessage::OverflowIdentifier => format!("LINE{:?}: Identifier length overflow!\n", self.line),
-            ErrorMessage::FailMatchingSemicolon => format!("LINE{:?}: Semicolon matching failed!\n", self.line),
-            ErrorMessage::MissingSemicolon => format!("LINE{:?}: missing a ';' at the end of the statement\n", self.line),
-            ErrorMessage::MissingLeftParenthesis => format!("LINE{:?}: expected '(' following the function statement\n", self.line),
-            ErrorMessage::MissingRightParenthesis => format!("LINE{:?}: expected ')' to cover the block\n", self.line),
-            ErrorMessage::MissingIf => format!("LINE{:?}: expected 'if' \n", self.line),
-            ErrorMessage::MissingThen => format!("LINE{:?}: expected 'then' \n", self.line),
-            ErrorMessage::MissingElse => format!("LINE{:?}: expected 'else' \n", self.line),
-            ErrorMessage::MissingMultiply => format!("LINE{:?}: expected '*' \n", self.line),
-            ErrorMessage::SyntaxErrorExpectedABlock => format!("LINE{:?}: syntax error, expected a block\n", self.line),
-            ErrorMessage::FailMatching => format!("LINE{:?}: Symbol matching error!\n", self.line),
-            ErrorMessage::MissingEnd => format!("LINE{:?}: missing END: this block is not covered\n", self.line),
-            ErrorMessage::ExpectedIdentifier => format!("LINE{:?}: Expected identifier in this field\n", self.line),
-            ErrorMessage::FoundRepeatDeclarationInThisField => format!("LINE{:?}: this symbol's declaration repeated in this field\n", self.line),
-        };
-        file.write_all(err_msg.as_bytes()).expect("Failed to write error file");
+    fn declare(&mut self, name: &str) {
+        // 记录说明的名字
+        self.declared.push(name.to_string());
     }
-    fn console_error(&self, errmsg: ErrorMessage) {
-        // 抛出错误
-        // 这里还是简化实现
-        // 应该扔到标准错误流中，写入文件
-        // 不能和标准输出流混合
-        // self.debug();
-        match errmsg {
-            ErrorMessage::SyntaxError => {
-                println!("LINE{:?}: syntax error, exited", self.line);
-            }
-            ErrorMessage::WrongReserveYouMeanFunction => {
-                println!("LINE{:?}: wrong reserve: you mean 'function'?", self.line);
+    fn keyword_suggestion(&self, target: &Expression) -> Option<&'static str> {
+        // 语句开头是未说明过的、与某个关键字拼写相近的标识符时，很可能是写错的关键字
+        // 其他单元导出的名字在语法分析时未知，按未说明处理
+        let Expression::Variable { name, .. } = target else { return None };
+        let declared = self.declared.iter().any(|d| match self.fold {
+            true => d.to_lowercase() == name.to_lowercase(),
+            false => d == name,
+        });
+        match declared {
+            true => None,
+            false => suggest(name, KEYWORDS.iter().map(|(k, _)| *k)),
+        }
+    }
+    fn enter(&mut self, name: &str) {
+        // 进入一个语法单元，打印当前记号
+        if self.trace {
+            eprintln!("[parse] {:indent$}> {} LINE{} '{}'", "", name, self.line, self.current_token().get_symbol(), indent = self.depth * 2);
+        }
+        self.depth += 1;
+    }
+    fn exit(&mut self, name: &str, ok: bool) {
+        // 退出一个语法单元，打印分析结果
+        self.depth -= 1;
+        if self.trace {
+            let res = if ok { "ok" } else { "error" };
+            eprintln!("[parse] {:indent$}< {} {}", "", name, res, indent = self.depth * 2);
+        }
+    }
+
+    fn parse_node_program(&mut self) -> Result<Ast, ErrorMessage>{
+        // <程序> → [<引用说明>] <分程序> | <单元>
+        traced!(self, "parse_node_program", {
+            if self.match_token(Token::Unit) {
+                return self.parse_node_unit();
             }
-            ErrorMessage::WrongReserveYouMeanRead => {
-                println!("LINE{:?}: wrong reserve: you mean 'read'?", self.line);
+            let uses = self.parse_node_uses()?;
+            let block = self.parse_node_block()?;
+            Ok(Ast { uses, unit: None, block })
+        })
+    }
+    fn parse_node_unit(&mut self) -> Result<Ast, ErrorMessage>{
+        // <单元> → unit <标识符>; [<引用说明>] interface <说明语句表> implementation <说明语句表> end
+        // 接口部分的过程和函数只写首部，首部之后直接是分号
+        traced!(self, "parse_node_unit", {
+            match self.match_token(Token::Unit) {
+                true => self.advance(),
+                false => return self.handle_error(ErrorMessage::SyntaxError)
             }
-            ErrorMessage::WrongReserveYouMeanWrite => {
-                println!("LINE{:?}: wrong reserve: you mean 'write'?", self.line);
+            let (name, span) = self.parse_node_identifier()?;
+            match self.match_token(Token::Semicolon) {
+                true => self.advance(),
+                false => return self.handle_error(ErrorMessage::MissingSemicolon)
             }
-            ErrorMessage::WrongAssignToken => {
-                println!("LINE{:?}: wrong assign operator: you mean ':='?", self.line);
+            let uses = self.parse_node_uses()?;
+            match self.match_token(Token::Interface) {
+                true => self.advance(),
+                false => return self.handle_error(ErrorMessage::MissingInterface)
             }
-            ErrorMessage::InvalidTypeExpectedInterger => {
-                println!("LINE{:?}: invalid type here, expected integer", self.line);
+            self.interface = true;
+            let interface = self.parse_node_declaration_statement_table()?;
+            self.interface = false;
+            match self.match_token(Token::Implementation) {
+                true => self.advance(),
+                false => return self.handle_error(ErrorMessage::MissingImplementation)
             }
-            ErrorMessage::InvalidNumber => {
-                println!("LINE{:?}: invalid number", self.line);
+            let declarations = self.parse_node_declaration_statement_table()?;
+            match self.match_token(Token::End) {
+                true => self.advance(),
+                false => return self.handle_error(ErrorMessage::MissingEnd)
             }
-            ErrorMessage::OverflowIdentifier => {
-                println!("LINE{:?}: the length of identifier overflows", self.line);
+            let block = Block { declarations, statements: Vec::new() };
+            Ok(Ast { uses, unit: Some(Unit { name, interface, span }), block })
+        })
+    }
+    fn parse_node_uses(&mut self) -> Result<Vec<(String, Span)>, ErrorMessage>{
+        // <引用说明> → uses <标识符>{,<标识符>};
+        traced!(self, "parse_node_uses", {
+            let mut uses = Vec::new();
+            if !self.match_token(Token::Uses) {
+                return Ok(uses);
             }
-            ErrorMessage::FailMatchingSemicolon => {
-                println!("LINE{:?}: fail in matching semicolon", self.line);
+            self.advance();
+            loop {
+                uses.push(self.parse_node_identifier()?);
+                match self.match_token(Token::Comma) {
+                    true => self.advance(),
+                    false => break,
+                }
             }
-            ErrorMessage::MissingSemicolon => {
-                println!("LINE{:?}: missing a ';' at the end of the statement", self.line);
+            match self.match_token(Token::Semicolon) {
+                true => self.advance(),
+                false => return self.handle_error(ErrorMessage::MissingSemicolon)
             }
-            ErrorMessage::MissingLeftParenthesis => {
-                println!("LINE{:?}: missing a '(' following the function statement", self.line);
+            Ok(uses)
+        })
+    }
+    fn parse_node_block(&mut self) -> Result<Block, ErrorMessage>{
+        // <分程序> → begin <说明语句表><执行语句表> end
+        traced!(self, "parse_node_block", {
+            match self.match_token(Token::Begin) {
+                true => self.advance(),
+                false => return self.handle_error(ErrorMessage::SyntaxErrorExpectedABlock)
             }
-            ErrorMessage::MissingRightParenthesis => {
-                println!("LINE{:?}: missing a ')' to cover the block", self.line);
+            let declarations = self.parse_node_declaration_statement_table()?;
+            let statements = self.parse_node_execution_statement_table()?;
+            match self.match_token(Token::End) {
+                true => self.advance(),
+                false => return self.handle_error(ErrorMessage::MissingEnd)
             }
-            ErrorMessage::MissingMultiply => {
-                println!("LINE{:?}: expected '*' ", self.line);
+            Ok(Block { declarations, statements })
+        })
+    }
+    fn parse_node_declaration_statement_table(&mut self) -> Result<Vec<Declaration>, ErrorMessage>{
+        // <说明语句表> → {<说明语句> ;}
+        traced!(self, "parse_node_declaration_statement_table", {
+            let mut declarations = Vec::new();
+            loop {
+                if !self.starts_declaration() {
+                    // 检查FOLLOW 集
+                    match self.current_token() {
+                        Token::Read | Token::Write | Token::Writeln | Token::If | Token::While | Token::Repeat | Token::For | Token::Case
+                        | Token::Begin | Token::Semicolon | Token::Identifier(_) | Token::End | Token::Implementation | Token::Eof => {
+                            return Ok(declarations);
+                        },
+                        _ => return self.handle_error(ErrorMessage::SyntaxError),
+                    }
+                }
+
+                // 匹配说明语句
+                declarations.extend(self.parse_node_declaration_statement()?);
+
+                // 匹配分号
+                match self.match_token(Token::Semicolon) {
+                    true => self.advance(),
+                    false => return self.handle_error(ErrorMessage::MissingSemicolon),
+                }
             }
-            ErrorMessage::MissingIf => {
-                println!("LINE{:?}: expected 'if' ", self.line);
+        })
+    }
+    fn starts_declaration(&mut self) -> bool {
+        // 当前记号能否开始一个说明语句
+        // 以标识符开头时，后面是标识符或function的是以类型名开头的说明，否则是执行语句
+        match self.current_token() {
+            Token::Integer | Token::Boolean | Token::Array | Token::Record
+            | Token::Procedure | Token::Const | Token::Type => true,
+            Token::Identifier(_) => matches!(self.peek_token(1), Token::Identifier(_) | Token::Function),
+            _ => false,
+        }
+    }
+    fn parse_node_declaration_statement(&mut self) -> Result<Vec<Declaration>, ErrorMessage>{
+        // <说明语句> → <类型> <说明语句'> | <过程说明> | <常量说明> | <类型说明>
+        traced!(self, "parse_node_declaration_statement", {
+            if self.match_token(Token::Procedure) {
+                return Ok(vec![self.parse_node_procedure_declaration()?]);
             }
-            ErrorMessage::MissingThen => {
-                println!("LINE{:?}: expected 'then' ", self.line);
+            if self.match_token(Token::Const) {
+                return self.parse_node_constant_declaration();
             }
-            ErrorMessage::MissingElse => {
-                println!("LINE{:?}: expected 'else' ", self.line);
+            if self.match_token(Token::Type) {
+                return self.parse_node_type_declaration();
             }
-            ErrorMessage::SyntaxErrorExpectedABlock => {
-                println!("LINE{:?}: syntax error, expected a block", self.line);
+            let vtype = self.parse_node_type()?;
+            self.parse_node_declaration_statement_prime(vtype)
+        })
+    }
+    fn parse_node_constant_declaration(&mut self) -> Result<Vec<Declaration>, ErrorMessage>{
+        // <常量说明> → const <常量定义>{; <常量定义>}
+        // 分号之后是 <标识符> = 时仍是常量定义，最后一个分号留给说明语句表
+        traced!(self, "parse_node_constant_declaration", {
+            match self.match_token(Token::Const) {
+                true => self.advance(),
+                false => return self.handle_error(ErrorMessage::SyntaxError)
             }
-            ErrorMessage::FailMatching => {
-                println!("LINE{:?}: 符号匹配错误!", self.line);
+            let mut constants = vec![self.parse_node_constant_definition()?];
+            while self.match_token(Token::Semicolon)
+                && matches!(self.peek_token(1), Token::Identifier(_))
+                && self.peek_token(2) == Token::Equal
+            {
+                self.advance();
+                constants.push(self.parse_node_constant_definition()?);
             }
-            ErrorMessage::MissingEnd => {
-                println!("LINE{:?}: missing END: this block is not covered", self.line);
+            Ok(constants)
+        })
+    }
+    fn parse_node_constant_definition(&mut self) -> Result<Declaration, ErrorMessage>{
+        // <常量定义> → <标识符> = <条件表达式>
+        traced!(self, "parse_node_constant_definition", {
+            let (name, span) = self.parse_node_identifier()?;
+            self.declare(&name);
+            match self.match_token(Token::Equal) {
+                true => self.advance(),
+                false => return self.handle_error(ErrorMessage::SyntaxError)
             }
-            ErrorMessage::ExpectedIdentifier => {
-                println!("LINE{:?}: expected indentifier", self.line);
+            let value = self.parse_node_condition()?;
+            Ok(Declaration::Constant { name, value, span })
+        })
+    }
+    fn parse_node_type_declaration(&mut self) -> Result<Vec<Declaration>, ErrorMessage>{
+        // <类型说明> → type <类型定义>{; <类型定义>}
+        // 与常量说明相同，分号之后是 <标识符> = 时仍是类型定义
+        traced!(self, "parse_node_type_declaration", {
+            match self.match_token(Token::Type) {
+                true => self.advance(),
+                false => return self.handle_error(ErrorMessage::SyntaxError)
             }
-            ErrorMessage::FoundRepeatDeclarationInThisField => {
-                println!("LINE{:?}: the declaration of this indentifier repeated in this scope", self.line);
+            let mut types = vec![self.parse_node_type_definition()?];
+            while self.match_token(Token::Semicolon)
+                && matches!(self.peek_token(1), Token::Identifier(_))
+                && self.peek_token(2) == Token::Equal
+            {
+                self.advance();
+                types.push(self.parse_node_type_definition()?);
             }
-        }
-        println!("-------------------------");
+            Ok(types)
+        })
     }
-    fn skip_bad_line(&mut self) {
-        let mut tk = self.current_token();
-        while tk != Token::Eol && tk != Token::Eof {
-            self.advance();
-            tk = self.current_token();
-        }
-    }
-    fn handle_error(&mut self, errmsg: ErrorMessage) -> Result<(), ErrorMessage>{
-        let res = Err(errmsg.clone());
-        self.debug();
-        self.error(errmsg);
-        self.skip_bad_line();
-        res
-    }
-    
-    fn parse_node_program(&mut self, env: &mut Env) -> Result<(), ErrorMessage>{
-        // <程序> → <分程序>
-        self.parse_node_block(env)
-    }
-    fn parse_node_block(&mut self, env: &mut Env) -> Result<(), ErrorMessage>{
-        // <分程序> → begin <说明语句表><执行语句表> end
-        match self.match_token(Token::Begin) {
-            true => self.advance(),
-            false => return self.handle_error(ErrorMessage::SyntaxErrorExpectedABlock)
-        }
-        env.enter_scope();
-        match self.parse_node_declaration_statement_table(env) {
-            Ok(_) => (),
-            Err(e) => return self.handle_error(e),
-        }
-        // match self.match_token(Token::Semicolon) {
-        //     true => self.advance(),
-        //     false => return self.handle_error(ErrorMessage::MissingSemicolon),
-        // }
-        match self.parse_node_execution_statement_table() {
-            Ok(_) => (),
-            Err(e) => return self.handle_error(e),
-        }
-        match self.match_token(Token::End) {
-            true => self.advance(),
-            false => return self.handle_error(ErrorMessage::MissingEnd)
-        }
-        env.exit_scope();
-        Ok(())
+    fn parse_node_type_definition(&mut self) -> Result<Declaration, ErrorMessage>{
+        // <类型定义> → <标识符> = <类型>
+        traced!(self, "parse_node_type_definition", {
+            let (name, span) = self.parse_node_identifier()?;
+            self.declare(&name);
+            match self.match_token(Token::Equal) {
+                true => self.advance(),
+                false => return self.handle_error(ErrorMessage::SyntaxError)
+            }
+            let spec = self.parse_node_type()?;
+            Ok(Declaration::Type { name, spec, span })
+        })
     }
-    fn parse_node_declaration_statement_table(&mut self, env:&mut Env) -> Result<(), ErrorMessage>{
-        // <说明语句表> → {<说明语句> ;}
-        loop {
-            if !self.match_token(Token::Integer) {
-                // 检查FOLLOW 集
-                match self.current_token() {
-                    Token::Read | Token::Write | Token::If | Token::Identifier(_) | Token::End | Token::Eof => {
-                        return Ok(());
-                    },
-                    _ => return self.handle_error(ErrorMessage::SyntaxError),
+    fn parse_node_procedure_declaration(&mut self) -> Result<Declaration, ErrorMessage>{
+        // <过程说明> → procedure <标识符>[(<形参表>)];(<函数体>|forward)
+        traced!(self, "parse_node_procedure_declaration", {
+            match self.match_token(Token::Procedure) {
+                true => self.advance(),
+                false => return self.handle_error(ErrorMessage::SyntaxError)
+            }
+            let (name, span) = self.parse_node_identifier()?;
+            self.declare(&name);
+            let mut parameters = Vec::new();
+            if self.match_token(Token::LeftParenthesis) {
+                self.advance();
+                parameters = self.parse_node_formal_parameter_list()?;
+                match self.match_token(Token::RightParenthesis) {
+                    true => self.advance(),
+                    false => return self.handle_error(ErrorMessage::MissingRightParenthesis)
                 }
             }
-            
-            // 匹配说明语句
-            match self.parse_node_declaration_statement(env) {
-                Ok(_) => (),
-                Err(e) => return self.handle_error(e),
+            // 接口部分的首部之后的分号属于说明语句表
+            if self.interface {
+                return Ok(Declaration::Forward { name, return_type: None, parameters, span });
             }
-            
-            // 匹配分号
             match self.match_token(Token::Semicolon) {
                 true => self.advance(),
                 false => return self.handle_error(ErrorMessage::MissingSemicolon),
             }
-        }
+            if self.match_token(Token::Forward) {
+                self.advance();
+                return Ok(Declaration::Forward { name, return_type: None, parameters, span });
+            }
+            let body = self.parse_node_function_body()?;
+            Ok(Declaration::Function { name, return_type: None, parameters, body, span })
+        })
     }
-    fn parse_node_declaration_statement(&mut self, env: &mut Env) -> Result<(), ErrorMessage>{
-        // <说明语句> → integer <说明语句'>
-        match self.match_token(Token::Integer) {
-            true => self.advance(),
-            false => return self.handle_error(ErrorMessage::InvalidTypeExpectedInterger)
-        }
-        self.parse_node_declaration_statement_prime(env)
+    fn parse_node_formal_parameter_list(&mut self) -> Result<Vec<Parameter>, ErrorMessage>{
+        // <形参表> → <形参>{,<形参>}
+        // <形参> → [var] <变量>
+        traced!(self, "parse_node_formal_parameter_list", {
+            let mut parameters = Vec::new();
+            loop {
+                let by_reference = self.match_token(Token::Var);
+                if by_reference {
+                    self.advance();
+                }
+                let (name, span) = self.parse_node_variable()?;
+                self.declare(&name);
+                parameters.push(Parameter { name, by_reference, span });
+                match self.match_token(Token::Comma) {
+                    true => self.advance(),
+                    false => return Ok(parameters),
+                }
+            }
+        })
     }
-    fn parse_node_declaration_statement_prime(&mut self, env: &mut Env) -> Result<(), ErrorMessage>{
-        // <说明语句'> → <变量> | function <标识符>（<参数>）<函数体> ;
-        if self.match_token(Token::Function) {
-            // 函数说明分支
-            self.advance();
-
-            // 获取函数标识符名称
-            let pname = match self.parse_node_identifier() {
-                Ok(pname) => pname,
-                Err(e) => return self.handle_error(e),
+    fn parse_node_type(&mut self) -> Result<TypeSpec, ErrorMessage>{
+        // <类型> → integer | boolean | <类型名> | <子界类型> | <数组类型> | <记录类型>
+        // <子界类型> → <算术表达式>..<算术表达式>
+        // 标识符之后是 .. 或运算符时是子界的下界，否则是类型名
+        traced!(self, "parse_node_type", {
+            let vtype = match self.current_token() {
+                Token::Integer => TypeSpec::Integer,
+                Token::Boolean => TypeSpec::Boolean,
+                Token::Array => return self.parse_node_array_type(),
+                Token::Record => return self.parse_node_record_type(),
+                Token::Identifier(name) => match self.peek_token(1) {
+                    Token::Range | Token::Plus | Token::Minus | Token::Multiply | Token::Div | Token::Mod => {
+                        return self.parse_node_subrange_type();
+                    }
+                    _ => TypeSpec::Named { name, span: self.current_span() },
+                },
+                Token::IntegerLiteral(_) | Token::Plus | Token::Minus | Token::LeftParenthesis => {
+                    return self.parse_node_subrange_type();
+                }
+                _ => return self.handle_error(ErrorMessage::InvalidTypeExpectedInterger),
             };
-            // 检查是否重复声明，若没有则添加声明
-            if env.check_repeat(pname.clone()){
-                return self.handle_error(ErrorMessage::FoundRepeatDeclarationInThisField);
-            } else{
-                env.add_procedure(pname.clone());
+            self.advance();
+            Ok(vtype)
+        })
+    }
+    fn parse_node_subrange_type(&mut self) -> Result<TypeSpec, ErrorMessage>{
+        // <子界类型> → <算术表达式>..<算术表达式>
+        traced!(self, "parse_node_subrange_type", {
+            let (low, high) = self.parse_node_index_range()?;
+            Ok(TypeSpec::Subrange { low, high })
+        })
+    }
+    fn parse_node_record_type(&mut self) -> Result<TypeSpec, ErrorMessage>{
+        // <记录类型> → record <域说明>{; <域说明>} end
+        // <域说明> → <类型> <变量表>，end 前的分号可以省略
+        traced!(self, "parse_node_record_type", {
+            match self.match_token(Token::Record) {
+                true => self.advance(),
+                false => return self.handle_error(ErrorMessage::SyntaxError)
             }
-
-            match self.match_token(Token::LeftParenthesis) {
+            let mut fields = Vec::new();
+            loop {
+                let ftype = self.parse_node_type()?;
+                for (name, span) in self.parse_node_variable_list()? {
+                    fields.push(Field { name, ftype: ftype.clone(), span });
+                }
+                if self.match_token(Token::Semicolon) {
+                    self.advance();
+                }
+                if self.match_token(Token::End) {
+                    self.advance();
+                    return Ok(TypeSpec::Record { fields });
+                }
+                if self.match_token(Token::Eof) {
+                    return self.handle_error(ErrorMessage::MissingEnd);
+                }
+            }
+        })
+    }
+    fn parse_node_array_type(&mut self) -> Result<TypeSpec, ErrorMessage>{
+        // array[<下标范围>{,<下标范围>}] of <类型>
+        traced!(self, "parse_node_array_type", {
+            match self.match_token(Token::Array) {
                 true => self.advance(),
-                false => return self.handle_error(ErrorMessage::MissingLeftParenthesis)
+                false => return self.handle_error(ErrorMessage::SyntaxError)
             }
-            match self.parse_node_parameter() {
-                Ok(_) => (),
-                Err(e) => return self.handle_error(e),
+            match self.match_token(Token::LeftBracket) {
+                true => self.advance(),
+                false => return self.handle_error(ErrorMessage::SyntaxError)
             }
-            match self.match_token(Token::RightParenthesis) {
+            let mut ranges = vec![self.parse_node_index_range()?];
+            while self.match_token(Token::Comma) {
+                self.advance();
+                ranges.push(self.parse_node_index_range()?);
+            }
+            match self.match_token(Token::RightBracket) {
                 true => self.advance(),
-                false => return self.handle_error(ErrorMessage::MissingRightParenthesis)
+                false => return self.handle_error(ErrorMessage::MissingRightBracket)
             }
-            match self.match_token(Token::Semicolon) {
-                true => {
+            match self.match_token(Token::Of) {
+                true => self.advance(),
+                false => return self.handle_error(ErrorMessage::MissingOf)
+            }
+            let element = self.parse_node_type()?;
+            Ok(ranges.into_iter().rev().fold(element, |element, (low, high)| TypeSpec::Array {
+                low,
+                high,
+                element: Box::new(element),
+            }))
+        })
+    }
+    fn parse_node_index_range(&mut self) -> Result<(Expression, Expression), ErrorMessage>{
+        // <下标范围> → <算术表达式>..<算术表达式>
+        // 上下界须是常量表达式，由语义检查求值
+        traced!(self, "parse_node_index_range", {
+            let low = self.parse_node_expression()?;
+            match self.match_token(Token::Range) {
+                true => self.advance(),
+                false => return self.handle_error(ErrorMessage::MissingRange)
+            }
+            let high = self.parse_node_expression()?;
+            Ok((low, high))
+        })
+    }
+    fn parse_node_declaration_statement_prime(&mut self, vtype: TypeSpec) -> Result<Vec<Declaration>, ErrorMessage>{
+        // <说明语句'> → <变量表> | function <标识符>（<形参表>）;(<函数体>|forward)
+        traced!(self, "parse_node_declaration_statement_prime", {
+            if self.match_token(Token::Function) {
+                // 函数说明分支
+                self.advance();
+
+                // 获取函数标识符名称
+                let (name, span) = self.parse_node_identifier()?;
+                self.declare(&name);
+
+                match self.match_token(Token::LeftParenthesis) {
+                    true => self.advance(),
+                    false => return self.handle_error(ErrorMessage::MissingLeftParenthesis)
+                }
+                let parameters = self.parse_node_formal_parameter_list()?;
+                match self.match_token(Token::RightParenthesis) {
+                    true => self.advance(),
+                    false => return self.handle_error(ErrorMessage::MissingRightParenthesis)
+                }
+                if self.interface {
+                    return Ok(vec![Declaration::Forward { name, return_type: Some(vtype), parameters, span }]);
+                }
+                match self.match_token(Token::Semicolon) {
+                    true => self.advance(),
+                    false => return self.handle_error(ErrorMessage::MissingSemicolon),
+                }
+                if self.match_token(Token::Forward) {
                     self.advance();
-                    match self.parse_node_function_body(env) {
-                        Ok(_) => Ok(()),
-                        Err(e) => return self.handle_error(e),
-                    }
-                },
-                false => self.handle_error(ErrorMessage::MissingSemicolon),
-            }
-        } else {
-            // 变量说明分支
-            // 获取变量名字
-            let vname = match self.parse_node_variable() {
-                Ok(vname) => vname,
-                Err(e) => return self.handle_error(e),
-            };
-            // 检查是否重复声明，若没有则添加声明
-            if env.check_repeat(vname.clone()){
-                return self.handle_error(ErrorMessage::FoundRepeatDeclarationInThisField);
-            } else{
-                env.add_variable(vname.clone(), "F".to_string(),0);
-                Ok(())
+                    return Ok(vec![Declaration::Forward { name, return_type: Some(vtype), parameters, span }]);
+                }
+                let body = self.parse_node_function_body()?;
+                Ok(vec![Declaration::Function { name, return_type: Some(vtype), parameters, body, span }])
+            } else {
+                // 变量说明分支，每个变量各是一个说明
+                let variables = self.parse_node_variable_list()?;
+                for (name, _) in &variables {
+                    self.declare(name);
+                }
+                Ok(variables
+                    .into_iter()
+                    .map(|(name, span)| Declaration::Variable { name, vtype: vtype.clone(), span })
+                    .collect())
             }
-        }
+        })
     }
-    fn parse_node_function_body(&mut self, env:&mut Env) -> Result<(), ErrorMessage>{
+    fn parse_node_variable_list(&mut self) -> Result<Vec<(String, Span)>, ErrorMessage>{
+        // <变量表> → <变量>{,<变量>}
+        traced!(self, "parse_node_variable_list", {
+            let mut variables = vec![self.parse_node_variable()?];
+            while self.match_token(Token::Comma) {
+                self.advance();
+                variables.push(self.parse_node_variable()?);
+            }
+            Ok(variables)
+        })
+    }
+    fn parse_node_function_body(&mut self) -> Result<Block, ErrorMessage>{
         // <函数体> → begin <说明语句表><执行语句表> end
-        match self.match_token(Token::Begin) {
-            true => self.advance(),
-            false => return self.handle_error(ErrorMessage::SyntaxErrorExpectedABlock)
-        }
-        match self.parse_node_declaration_statement_table(env) {
-            Ok(_) => (),
-            Err(e) => return self.handle_error(e),
-        }
-        // match self.match_token(Token::Semicolon) {
-        //     true => self.advance(),
-        //     false => return self.handle_error(ErrorMessage::MissingSemicolon)
-        // }
-        match self.parse_node_execution_statement_table() {
-            Ok(_) => (),
-            Err(e) => return self.handle_error(e),
-        }
-        match self.match_token(Token::End) {
-            true => {
+        traced!(self, "parse_node_function_body", {
+            self.parse_node_block()
+        })
+    }
+    fn parse_node_parameter_list(&mut self) -> Result<Vec<Expression>, ErrorMessage>{
+        // <参数表> → <参数>{,<参数>}
+        traced!(self, "parse_node_parameter_list", {
+            let mut arguments = vec![self.parse_node_parameter()?];
+            while self.match_token(Token::Comma) {
                 self.advance();
-                Ok(())
-            },
-            false => return self.handle_error(ErrorMessage::MissingEnd)
-        }
+                arguments.push(self.parse_node_parameter()?);
+            }
+            Ok(arguments)
+        })
     }
-    fn parse_node_parameter(&mut self) -> Result<(), ErrorMessage>{
-        // <参数> → <算术表达式>
-        match self.parse_node_expression() {
-            Ok(_) => Ok(()),
-            Err(e) => return self.handle_error(e),
-        }
+    fn parse_node_parameter(&mut self) -> Result<Expression, ErrorMessage>{
+        // <参数> → <条件表达式>
+        traced!(self, "parse_node_parameter", {
+            self.parse_node_condition()
+        })
+    }
+    fn parse_node_execution_statement_table(&mut self) -> Result<Vec<Statement>, ErrorMessage>{
+        // <执行语句表> → <执行语句> {; <执行语句>}
+        // FOLLOW(<执行语句表>) 包含 'end'、'until' 和 '$'
+        // 空语句使得 end 前多写的分号也合法，与课程文法的 {<执行语句> ;} 兼容
+        traced!(self, "parse_node_execution_statement_table", {
+            let mut statements = Vec::new();
+            loop {
+                // 检查当前token是否可以开始一个执行语句
+                match self.current_token() {
+                    Token::Read | Token::Write | Token::Writeln | Token::If | Token::While | Token::Repeat | Token::For | Token::Case
+                    | Token::Begin | Token::Semicolon | Token::Identifier(_) => {
+                        // 可以开始执行语句，继续解析
+                    },
+                    Token::End | Token::Until | Token::Eof => {
+                        // 否则，检查是否在 FOLLOW 集里 (end、until 或 EOF)
+                        // 如果在，说明执行语句表结束
+                        return Ok(statements);
+                    },
+                    _ => return self.handle_error(ErrorMessage::SyntaxError),
+                }
+
+                // 解析一个执行语句
+                statements.push(self.parse_node_execution_statement()?);
+
+                // 期望匹配分号，最后一个语句之后可以省略
+                match self.current_token() {
+                    Token::Semicolon => self.advance(),
+                    Token::End | Token::Until | Token::Eof => return Ok(statements),
+                    _ => return self.handle_error(ErrorMessage::MissingSemicolon),
+                }
+            }
+        })
     }
-    fn parse_node_execution_statement_table(&mut self) -> Result<(), ErrorMessage>{
-        // <执行语句表> → {<执行语句> ;}
-        // FOLLOW(<执行语句表>) 包含 'end' 和 '$'
-        loop {
-            // 检查当前token是否可以开始一个执行语句
+    fn parse_node_execution_statement(&mut self) -> Result<Statement, ErrorMessage>{
+        // <执行语句> → <读语句>│<写语句>│<赋值语句>│<调用语句>│<条件语句>│<当型循环语句>│<直到型循环语句>│<计数循环语句>│<情况语句>│<复合语句>│<空语句>
+        traced!(self, "parse_node_execution_statement", {
             match self.current_token() {
-                Token::Read | Token::Write | Token::If | Token::Identifier(_) => {
-                    // 可以开始执行语句，继续解析
+                Token::Begin => self.parse_node_compound_statement(),
+                Token::Semicolon | Token::End | Token::Until | Token::Else => {
+                    // <空语句> → ε，不消耗记号
+                    Ok(Statement::Empty { span: self.current_span() })
                 },
-                Token::End | Token::Eof => {
-                    // 否则，检查是否在 FOLLOW 集里 (end 或 EOF)
-                    // 如果在，说明执行语句表结束
-                    return Ok(());
+                Token::Read => self.parse_node_read_statement(),
+                Token::Write | Token::Writeln => self.parse_node_write_statement(),
+                Token::If => self.parse_node_conditional_statement(),
+                Token::While => self.parse_node_while_statement(),
+                Token::Repeat => self.parse_node_repeat_statement(),
+                Token::For => self.parse_node_for_statement(),
+                Token::Case => self.parse_node_case_statement(),
+                Token::Identifier(_) => {
+                    // 标识符之后是语句的结束或左括号时为调用语句，否则按赋值语句分析
+                    match self.peek_token(1) {
+                        Token::LeftParenthesis | Token::Semicolon | Token::End | Token::Until | Token::Else | Token::Eof => {
+                            self.parse_node_call_statement()
+                        }
+                        _ => self.parse_node_assignment_statement(),
+                    }
                 },
-                _ => return self.handle_error(ErrorMessage::SyntaxError),
+                _ => self.handle_error(ErrorMessage::SyntaxError)
             }
-            
-            // 解析一个执行语句
-            match self.parse_node_execution_statement() {
-                Ok(_) => (),
-                Err(e) => return self.handle_error(e),
+        })
+    }
+    fn parse_node_assignment_statement(&mut self) -> Result<Statement, ErrorMessage>{
+        // <赋值语句> → <变量访问> := <条件表达式>
+        traced!(self, "parse_node_assignment_statement", {
+            let span = self.current_span();
+            let target = self.parse_node_variable_access()?;
+            match self.match_token(Token::Assign) {
+                true => self.advance(),
+                false => {
+                    let suggestion = self.keyword_suggestion(&target);
+                    return self.handle_error(with_suggestion(ErrorMessage::WrongAssignToken, suggestion))
+                }
             }
-            
-            // 期望匹配分号
-            match self.match_token(Token::Semicolon) {
+            let value = self.parse_node_condition()?;
+            Ok(Statement::Assign { target, value, span })
+        })
+    }
+    fn parse_node_call_statement(&mut self) -> Result<Statement, ErrorMessage>{
+        // <调用语句> → <标识符>[(<参数表>)]
+        traced!(self, "parse_node_call_statement", {
+            let (name, span) = self.parse_node_identifier()?;
+            let mut arguments = Vec::new();
+            if self.match_token(Token::LeftParenthesis) {
+                self.advance();
+                arguments = self.parse_node_parameter_list()?;
+                match self.match_token(Token::RightParenthesis) {
+                    true => self.advance(),
+                    false => return self.handle_error(ErrorMessage::MissingRightParenthesis)
+                }
+            }
+            Ok(Statement::Call { name, arguments, span })
+        })
+    }
+    fn parse_node_conditional_statement(&mut self) -> Result<Statement, ErrorMessage>{
+        // <条件语句> → if<条件表达式>then<执行语句> [else <执行语句>]
+        // 悬空的else与最近的if配对：内层条件语句先看到else就把它消耗掉
+        traced!(self, "parse_node_conditional_statement", {
+            let span = self.current_span();
+            match self.match_token(Token::If) {
                 true => self.advance(),
-                false => return self.handle_error(ErrorMessage::MissingSemicolon),
+                false => return self.handle_error(ErrorMessage::MissingIf)
             }
-        }
+            let condition = self.parse_node_condition()?;
+            match self.match_token(Token::Then) {
+                true => self.advance(),
+                false => return self.handle_error(ErrorMessage::MissingThen)
+            }
+            let then_branch = self.parse_node_execution_statement()?;
+            let else_branch = match self.match_token(Token::Else) {
+                true => {
+                    self.advance();
+                    Some(Box::new(self.parse_node_execution_statement()?))
+                }
+                false => None,
+            };
+            Ok(Statement::If {
+                condition,
+                then_branch: Box::new(then_branch),
+                else_branch,
+                span,
+            })
+        })
     }
-    fn parse_node_execution_statement(&mut self) -> Result<(), ErrorMessage>{
-        // <执行语句> → <读语句>│<写语句>│<赋值语句>│<条件语句>
-        match self.current_token() {
-            Token::Read => self.parse_node_read_statement(),
-            Token::Write => self.parse_node_write_statement(),
-            Token::If => self.parse_node_conditional_statement(),
-            Token::Identifier(_) => self.parse_node_assignment_statement(),
-            _ => self.handle_error(ErrorMessage::SyntaxError)
-        }
+    fn parse_node_compound_statement(&mut self) -> Result<Statement, ErrorMessage>{
+        // <复合语句> → begin <执行语句表> end
+        traced!(self, "parse_node_compound_statement", {
+            let span = self.current_span();
+            match self.match_token(Token::Begin) {
+                true => self.advance(),
+                false => return self.handle_error(ErrorMessage::SyntaxErrorExpectedABlock)
+            }
+            let statements = self.parse_node_execution_statement_table()?;
+            match self.match_token(Token::End) {
+                true => self.advance(),
+                false => return self.handle_error(ErrorMessage::MissingEnd)
+            }
+            Ok(Statement::Compound { statements, span })
+        })
     }
-    fn parse_node_assignment_statement(&mut self) -> Result<(), ErrorMessage>{
-        // <赋值语句> → <变量> := <算术表达式>
-        match self.parse_node_variable() {
-            Ok(_) => (),
-            Err(e) => return self.handle_error(e),
-        }
-        match self.match_token(Token::Assign) {
-            true => self.advance(),
-            false => return self.handle_error(ErrorMessage::WrongAssignToken)
-        }
-        match self.parse_node_expression() {
-            Ok(_) => Ok(()),
-            Err(e) => return self.handle_error(e),
-        }
+    fn parse_node_while_statement(&mut self) -> Result<Statement, ErrorMessage>{
+        // <当型循环语句> → while<条件表达式>do<执行语句>
+        traced!(self, "parse_node_while_statement", {
+            let span = self.current_span();
+            match self.match_token(Token::While) {
+                true => self.advance(),
+                false => return self.handle_error(ErrorMessage::SyntaxError)
+            }
+            let condition = self.parse_node_condition()?;
+            match self.match_token(Token::Do) {
+                true => self.advance(),
+                false => return self.handle_error(ErrorMessage::MissingDo)
+            }
+            let body = self.parse_node_execution_statement()?;
+            Ok(Statement::While {
+                condition,
+                body: Box::new(body),
+                span,
+            })
+        })
     }
-    fn parse_node_conditional_statement(&mut self) -> Result<(), ErrorMessage>{
-        // <条件语句> → if<条件表达式>then<执行语句>else <执行语句>
-        match self.match_token(Token::If) {
-            true => self.advance(),
-            false => return self.handle_error(ErrorMessage::MissingIf)
-        }
-        match self.parse_node_condition() {
-            Ok(_) => (),
-            Err(e) => return self.handle_error(e),
-        }
-        match self.match_token(Token::Then) {
-            true => self.advance(),
-            false => return self.handle_error(ErrorMessage::MissingThen)
-        }
-        match self.parse_node_execution_statement() {
-            Ok(_) => (),
-            Err(e) => return self.handle_error(e),
-        }
-        match self.match_token(Token::Else) {
-            true => self.advance(),
-            false => return self.handle_error(ErrorMessage::MissingElse)
-        }
-        match self.parse_node_execution_statement() {
-            Ok(_) => Ok(()),
-            Err(e) => return self.handle_error(e),
-        }
+    fn parse_node_repeat_statement(&mut self) -> Result<Statement, ErrorMessage>{
+        // <直到型循环语句> → repeat<执行语句表>until<条件表达式>
+        traced!(self, "parse_node_repeat_statement", {
+            let span = self.current_span();
+            match self.match_token(Token::Repeat) {
+                true => self.advance(),
+                false => return self.handle_error(ErrorMessage::SyntaxError)
+            }
+            let body = self.parse_node_execution_statement_table()?;
+            match self.match_token(Token::Until) {
+                true => self.advance(),
+                false => return self.handle_error(ErrorMessage::MissingUntil)
+            }
+            let condition = self.parse_node_condition()?;
+            Ok(Statement::Repeat { body, condition, span })
+        })
     }
-    fn parse_node_read_statement(&mut self) -> Result<(), ErrorMessage>{
-        // <读语句> → read(<变量>)
-        match self.match_token(Token::Read) {
-            true => self.advance(),
-            false => return self.handle_error(ErrorMessage::WrongReserveYouMeanRead)
-        }
-        match self.match_token(Token::LeftParenthesis) {
-            true => self.advance(),
-            false => return self.handle_error(ErrorMessage::MissingLeftParenthesis)
-        }
-        match self.parse_node_variable() {
-            Ok(_) => (),
-            Err(e) => return self.handle_error(e),
-        }
-        match self.match_token(Token::RightParenthesis) {
-            true => {
-                self.advance();
-                Ok(())
-            },
-            false => return self.handle_error(ErrorMessage::MissingRightParenthesis)
-        }
+    fn parse_node_for_statement(&mut self) -> Result<Statement, ErrorMessage>{
+        // <计数循环语句> → for <变量> := <条件表达式> (to|downto) <条件表达式> do <执行语句>
+        traced!(self, "parse_node_for_statement", {
+            let span = self.current_span();
+            match self.match_token(Token::For) {
+                true => self.advance(),
+                false => return self.handle_error(ErrorMessage::SyntaxError)
+            }
+            let (variable, variable_span) = self.parse_node_variable()?;
+            match self.match_token(Token::Assign) {
+                true => self.advance(),
+                false => return self.handle_error(ErrorMessage::WrongAssignToken)
+            }
+            let start = self.parse_node_condition()?;
+            let downward = match self.current_token() {
+                Token::To => false,
+                Token::Downto => true,
+                _ => return self.handle_error(ErrorMessage::MissingTo),
+            };
+            self.advance();
+            let end = self.parse_node_condition()?;
+            match self.match_token(Token::Do) {
+                true => self.advance(),
+                false => return self.handle_error(ErrorMessage::MissingDo)
+            }
+            let body = self.parse_node_execution_statement()?;
+            Ok(Statement::For {
+                variable,
+                variable_span,
+                start,
+                end,
+                downward,
+                body: Box::new(body),
+                span,
+            })
+        })
     }
-    fn parse_node_write_statement(&mut self) -> Result<(), ErrorMessage>{
-        // <写语句> → write(<变量>)
-        match self.match_token(Token::Write) {
-            true => self.advance(),
-            false => return self.handle_error(ErrorMessage::WrongReserveYouMeanWrite)
-        }
-        match self.match_token(Token::LeftParenthesis) {
-            true => self.advance(),
-            false => return self.handle_error(ErrorMessage::MissingLeftParenthesis)
-        }
-        match self.parse_node_variable() {
-            Ok(_) => (),
-            Err(e) => return self.handle_error(e),
-        }
-        match self.match_token(Token::RightParenthesis) {
-            true => {
-                self.advance();
-                Ok(())
-            },
-            false => return self.handle_error(ErrorMessage::MissingRightParenthesis)
-        }
+    fn parse_node_case_statement(&mut self) -> Result<Statement, ErrorMessage>{
+        // <情况语句> → case <条件表达式> of <情况分支>{; <情况分支>} [;] [else <执行语句表>] end
+        traced!(self, "parse_node_case_statement", {
+            let span = self.current_span();
+            match self.match_token(Token::Case) {
+                true => self.advance(),
+                false => return self.handle_error(ErrorMessage::SyntaxError)
+            }
+            let selector = self.parse_node_condition()?;
+            match self.match_token(Token::Of) {
+                true => self.advance(),
+                false => return self.handle_error(ErrorMessage::MissingOf)
+            }
+            let mut arms = vec![self.parse_node_case_arm()?];
+            loop {
+                if self.match_token(Token::Semicolon) {
+                    self.advance();
+                }
+                match self.current_token() {
+                    Token::Else | Token::End => break,
+                    Token::Eof => return self.handle_error(ErrorMessage::MissingEnd),
+                    _ => arms.push(self.parse_node_case_arm()?),
+                }
+            }
+            let otherwise = match self.match_token(Token::Else) {
+                true => {
+                    self.advance();
+                    Some(self.parse_node_execution_statement_table()?)
+                }
+                false => None,
+            };
+            match self.match_token(Token::End) {
+                true => self.advance(),
+                false => return self.handle_error(ErrorMessage::MissingEnd)
+            }
+            Ok(Statement::Case { selector, arms, otherwise, span })
+        })
     }
-    fn parse_node_condition(&mut self) -> Result<(), ErrorMessage>{
-        // <条件表达式> → <算术表达式><关系运算符><算术表达式>
-        match self.parse_node_expression() {
-            Ok(_) => (),
-            Err(e) => return self.handle_error(e),
-        }
-        match self.parse_node_relational_operator() {
-            Ok(_) => self.advance(),
-            Err(e) => return self.handle_error(e),
-        }
-        match self.parse_node_expression() {
-            Ok(_) => (),
-            Err(e) => return self.handle_error(e),
-        }
-        Ok(())
+    fn parse_node_case_arm(&mut self) -> Result<CaseArm, ErrorMessage>{
+        // <情况分支> → <情况标号>{,<情况标号>} : <执行语句>
+        // <情况标号> → <算术表达式>[..<算术表达式>]
+        traced!(self, "parse_node_case_arm", {
+            let mut labels = Vec::new();
+            loop {
+                let low = self.parse_node_expression()?;
+                let high = match self.match_token(Token::Range) {
+                    true => {
+                        self.advance();
+                        Some(self.parse_node_expression()?)
+                    }
+                    false => None,
+                };
+                labels.push(CaseLabel { low, high });
+                match self.match_token(Token::Comma) {
+                    true => self.advance(),
+                    false => break,
+                }
+            }
+            match self.match_token(Token::Colon) {
+                true => self.advance(),
+                false => return self.handle_error(ErrorMessage::MissingColon)
+            }
+            let body = self.parse_node_execution_statement()?;
+            Ok(CaseArm { labels, body })
+        })
     }
-    fn parse_node_expression(&mut self) -> Result<(), ErrorMessage>{
-        // <算术表达式> → <项> <算术表达式'>
-        match self.parse_node_term() {
-            Ok(_) => (),
-            Err(e) => return self.handle_error(e),
-        }
-        match self.parse_node_expression_prime() {
-            Ok(_) => (),
-            Err(e) => return self.handle_error(e),
-        }
-        Ok(())
+    fn parse_node_read_statement(&mut self) -> Result<Statement, ErrorMessage>{
+        // <读语句> → read(<变量访问>)
+        traced!(self, "parse_node_read_statement", {
+            match self.match_token(Token::Read) {
+                true => self.advance(),
+                false => return self.handle_error(ErrorMessage::SyntaxError)
+            }
+            match self.match_token(Token::LeftParenthesis) {
+                true => self.advance(),
+                false => return self.handle_error(ErrorMessage::MissingLeftParenthesis)
+            }
+            let span = self.current_span();
+            let target = self.parse_node_variable_access()?;
+            match self.match_token(Token::RightParenthesis) {
+                true => self.advance(),
+                false => return self.handle_error(ErrorMessage::MissingRightParenthesis)
+            }
+            Ok(Statement::Read { target, span })
+        })
     }
-    fn parse_node_expression_prime(&mut self) -> Result<(), ErrorMessage>{
-        // <算术表达式'> → -<项> <算术表达式'> | ε
-        if self.match_token(Token::Minus) {
+    fn parse_node_write_statement(&mut self) -> Result<Statement, ErrorMessage>{
+        // <写语句> → write(<输出项表>) | writeln[(<输出项表>)]
+        // <输出项表> → <条件表达式>{,<条件表达式>}
+        traced!(self, "parse_node_write_statement", {
+            let span = self.current_span();
+            let newline = match self.current_token() {
+                Token::Write => false,
+                Token::Writeln => true,
+                _ => return self.handle_error(ErrorMessage::SyntaxError)
+            };
             self.advance();
-            match self.parse_node_term() {
-                Ok(_) => (),
-                Err(e) => return self.handle_error(e),
+            // writeln 可以不带输出项，只输出换行
+            if newline && !self.match_token(Token::LeftParenthesis) {
+                return Ok(Statement::Write { arguments: Vec::new(), newline, span });
             }
-            match self.parse_node_expression_prime() {
-                Ok(_) => (),
-                Err(e) => return self.handle_error(e),
+            match self.match_token(Token::LeftParenthesis) {
+                true => self.advance(),
+                false => return self.handle_error(ErrorMessage::MissingLeftParenthesis)
             }
-            Ok(())
-        }else { 
-            Ok(())
-        }
+            let arguments = self.parse_node_parameter_list()?;
+            match self.match_token(Token::RightParenthesis) {
+                true => self.advance(),
+                false => return self.handle_error(ErrorMessage::MissingRightParenthesis)
+            }
+            Ok(Statement::Write { arguments, newline, span })
+        })
+    }
+    fn parse_node_condition(&mut self) -> Result<Expression, ErrorMessage>{
+        // <条件表达式> → <算术表达式> [<关系运算符><算术表达式>]
+        // 关系运算符优先级最低，没有关系运算符时就是一个算术表达式
+        traced!(self, "parse_node_condition", {
+            let left = self.parse_node_expression()?;
+            let span = self.current_span();
+            let operator = match self.current_token() {
+                Token::Equal | Token::NotEqual | Token::Less | Token::LessEqual | Token::Greater | Token::GreaterEqual => {
+                    self.parse_node_relational_operator()?
+                }
+                _ => return Ok(left),
+            };
+            self.advance();
+            let right = self.parse_node_expression()?;
+            Ok(Expression::Binary {
+                operator,
+                left: Box::new(left),
+                right: Box::new(right),
+                span,
+            })
+        })
     }
-    fn parse_node_term(&mut self) -> Result<(), ErrorMessage>{
+    fn parse_node_expression(&mut self) -> Result<Expression, ErrorMessage>{
+        // <算术表达式> → [+|-]<项> <算术表达式'>
+        // 正负号作用于第一个项，-a*b 即 -(a*b)
+        traced!(self, "parse_node_expression", {
+            let operator = match self.current_token() {
+                Token::Plus => Some(Operator::Plus),
+                Token::Minus => Some(Operator::Minus),
+                _ => None,
+            };
+            let left = match operator {
+                Some(operator) => {
+                    let span = self.current_span();
+                    self.advance();
+                    let operand = self.parse_node_term()?;
+                    Expression::Unary { operator, operand: Box::new(operand), span }
+                }
+                None => self.parse_node_term()?,
+            };
+            self.parse_node_expression_prime(left)
+        })
+    }
+    fn parse_node_expression_prime(&mut self, left: Expression) -> Result<Expression, ErrorMessage>{
+        // <算术表达式'> → <加法运算符><项> <算术表达式'> | ε
+        // <加法运算符> → + | - | or
+        // 已分析的部分作为左操作数传入，保证左结合
+        traced!(self, "parse_node_expression_prime", {
+            let operator = match self.current_token() {
+                Token::Plus => Operator::Plus,
+                Token::Minus => Operator::Minus,
+                Token::Or => Operator::Or,
+                _ => return Ok(left),
+            };
+            let span = self.current_span();
+            self.advance();
+            let right = self.parse_node_term()?;
+            let left = Expression::Binary {
+                operator,
+                left: Box::new(left),
+                right: Box::new(right),
+                span,
+            };
+            self.parse_node_expression_prime(left)
+        })
+    }
+    fn parse_node_term(&mut self) -> Result<Expression, ErrorMessage>{
         // <项> → <因子> <项'>
-        match self.parse_node_factor() {
-            Ok(_) => (),
-            Err(e) => return self.handle_error(e),
-        }
-        match self.parse_node_term_prime() {
-            Ok(_) => (),
-            Err(e) => return self.handle_error(e),
-        }
-        Ok(())
+        traced!(self, "parse_node_term", {
+            let left = self.parse_node_factor()?;
+            self.parse_node_term_prime(left)
+        })
     }
-    fn parse_node_term_prime(&mut self) -> Result<(), ErrorMessage>{
-        // <项'> → *<因子> <项'> | ε
-        if self.match_token(Token::Multiply) {
+    fn parse_node_term_prime(&mut self, left: Expression) -> Result<Expression, ErrorMessage>{
+        // <项'> → <乘法运算符><因子> <项'> | ε
+        // <乘法运算符> → * | div | mod | and
+        traced!(self, "parse_node_term_prime", {
+            let operator = match self.current_token() {
+                Token::Multiply => Operator::Multiply,
+                Token::Div => Operator::Divide,
+                Token::Mod => Operator::Modulo,
+                Token::And => Operator::And,
+                _ => return Ok(left),
+            };
+            let span = self.current_span();
             self.advance();
-            match self.parse_node_factor() {
-                Ok(_) => (),
-                Err(e) => return self.handle_error(e),
+            let right = self.parse_node_factor()?;
+            let left = Expression::Binary {
+                operator,
+                left: Box::new(left),
+                right: Box::new(right),
+                span,
+            };
+            self.parse_node_term_prime(left)
+        })
+    }
+    fn parse_node_factor(&mut self) -> Result<Expression, ErrorMessage>{
+        // <因子> → <标识符> <因子后缀> | <常数> | <字符串> | true | false | not <因子> | (<条件表达式>)
+        traced!(self, "parse_node_factor", {
+            match self.current_token() {
+                Token::LeftParenthesis => {
+                    self.advance();
+                    let expr = self.parse_node_condition()?;
+                    match self.match_token(Token::RightParenthesis) {
+                        true => {
+                            self.advance();
+                            Ok(expr)
+                        },
+                        false => self.handle_error(ErrorMessage::MissingRightParenthesis)
+                    }
+                },
+                Token::IntegerLiteral(_) => self.parse_node_constant(),
+                Token::StringLiteral(value) => {
+                    let span = self.current_span();
+                    self.advance();
+                    Ok(Expression::String { value, span })
+                },
+                Token::True | Token::False => {
+                    let value = self.match_token(Token::True);
+                    let span = self.current_span();
+                    self.advance();
+                    Ok(Expression::Boolean { value, span })
+                },
+                Token::Not => {
+                    let span = self.current_span();
+                    self.advance();
+                    let operand = self.parse_node_factor()?;
+                    Ok(Expression::Unary { operator: Operator::Not, operand: Box::new(operand), span })
+                },
+                Token::Identifier(_) => {
+                    let (name, span) = self.parse_node_identifier()?;
+                    self.parse_node_factor_suffix(name, span)
+                },
+                _ => self.handle_error(ErrorMessage::SyntaxError)
             }
-            match self.parse_node_term_prime() {
-                Ok(_) => (),
-                Err(e) => return self.handle_error(e),
+        })
+    }
+    fn parse_node_factor_suffix(&mut self, name: String, span: Span) -> Result<Expression, ErrorMessage>{
+        // <因子后缀> → (<参数表>)| <选择后缀>
+        traced!(self, "parse_node_factor_suffix", {
+            match self.match_token(Token::LeftParenthesis) {
+                true => self.advance(),
+                false => return self.parse_node_selector_suffix(Expression::Variable { name, span }),
             }
-            Ok(())
-        }else {
-            Ok(())
-        }
+            let arguments = self.parse_node_parameter_list()?;
+            match self.match_token(Token::RightParenthesis) {
+                true => self.advance(),
+                false => return self.handle_error(ErrorMessage::MissingRightParenthesis)
+            }
+            Ok(Expression::Call { name, arguments, span })
+        })
     }
-    fn parse_node_factor(&mut self) -> Result<(), ErrorMessage>{
-        // <因子> → <标识符> <因子后缀> | <常数> | (<算术表达式>)
-        match self.current_token() {
-            Token::LeftParenthesis => {
-                self.advance();
-                match self.parse_node_expression() {
-                    Ok(_) => (),
-                    Err(e) => return self.handle_error(e),
-                }
-                match self.match_token(Token::RightParenthesis) {
-                    true => {
+    fn parse_node_variable_access(&mut self) -> Result<Expression, ErrorMessage>{
+        // <变量访问> → <变量> <选择后缀>
+        traced!(self, "parse_node_variable_access", {
+            let (name, span) = self.parse_node_variable()?;
+            self.parse_node_selector_suffix(Expression::Variable { name, span })
+        })
+    }
+    fn parse_node_selector_suffix(&mut self, base: Expression) -> Result<Expression, ErrorMessage>{
+        // <选择后缀> → {[<算术表达式>{,<算术表达式>}] | .<标识符>}
+        // a[i, j] 与 a[i][j] 相同，已分析的部分作为被选择的数组或记录传入
+        traced!(self, "parse_node_selector_suffix", {
+            let mut base = base;
+            loop {
+                match self.current_token() {
+                    Token::LeftBracket => {
+                        loop {
+                            let span = self.current_span();
+                            self.advance();
+                            let index = self.parse_node_expression()?;
+                            base = Expression::Index { array: Box::new(base), index: Box::new(index), span };
+                            if !self.match_token(Token::Comma) {
+                                break;
+                            }
+                        }
+                        match self.match_token(Token::RightBracket) {
+                            true => self.advance(),
+                            false => return self.handle_error(ErrorMessage::MissingRightBracket)
+                        }
+                    }
+                    Token::Dot => {
                         self.advance();
-                        Ok(())
-                    },
-                    false => self.handle_error(ErrorMessage::MissingRightParenthesis)
-                }
-            },
-            Token::IntegerLiteral(_) => self.parse_node_constant(),
-            Token::Identifier(_) => {
-                match self.parse_node_identifier() {
-                    Ok(_) => (),
-                    Err(e) => return self.handle_error(e),
-                }
-                match self.parse_node_factor_suffix() {
-                    Ok(_) => (),
-                    Err(e) => return self.handle_error(e),
+                        let (field, span) = self.parse_node_identifier()?;
+                        base = Expression::Field { record: Box::new(base), field, span };
+                    }
+                    _ => return Ok(base),
                 }
-                Ok(())
-            },
-            _ => self.handle_error(ErrorMessage::SyntaxError)
-        }
-    }
-    fn parse_node_factor_suffix(&mut self) -> Result<(), ErrorMessage>{
-        // <因子后缀> → (<参数>)| ε
-        match self.match_token(Token::LeftParenthesis) {
-            true => self.advance(),
-            false => return Ok(()),
-        }
-        match self.parse_node_parameter() {
-            Ok(_) => (),
-            Err(e) => return self.handle_error(e),
-        }
-        match self.match_token(Token::RightParenthesis) {
-            true => self.advance(),
-            false => return self.handle_error(ErrorMessage::MissingRightParenthesis)
-        }
-        Ok(())
+            }
+        })
     }
-    fn parse_node_relational_operator(&mut self) -> Result<(), ErrorMessage>{
+    fn parse_node_relational_operator(&mut self) -> Result<Operator, ErrorMessage>{
         // <关系运算符> → <│<=│>│>=│=│<>
-        match self.current_token() {
-            Token::Equal => Ok(()),
-            Token::NotEqual => Ok(()),
-            Token::Less => Ok(()),
-            Token::LessEqual => Ok(()),
-            Token::Greater => Ok(()),
-            Token::GreaterEqual => Ok(()),
-            _ => self.handle_error(ErrorMessage::SyntaxError)
-        }
+        traced!(self, "parse_node_relational_operator", {
+            match self.current_token() {
+                Token::Equal => Ok(Operator::Equal),
+                Token::NotEqual => Ok(Operator::NotEqual),
+                Token::Less => Ok(Operator::Less),
+                Token::LessEqual => Ok(Operator::LessEqual),
+                Token::Greater => Ok(Operator::Greater),
+                Token::GreaterEqual => Ok(Operator::GreaterEqual),
+                _ => self.handle_error(ErrorMessage::SyntaxError)
+            }
+        })
     }
-    fn parse_node_variable(&mut self) -> Result<String, ErrorMessage>{
+    fn parse_node_variable(&mut self) -> Result<(String, Span), ErrorMessage>{
         // <变量> → <标识符>
-        match self.parse_node_identifier() {
-            Ok(name) => Ok(name),
-            Err(e) => return Err(e),
-        }
+        traced!(self, "parse_node_variable", {
+            self.parse_node_identifier()
+        })
     }
-    fn parse_node_constant(&mut self) -> Result<(), ErrorMessage>{
+    fn parse_node_constant(&mut self) -> Result<Expression, ErrorMessage>{
         // <常量> → <整数>
-        match self.current_token() {
-            Token::IntegerLiteral(_) => {
-                self.advance();
-                Ok(())
-            },
-            _ => self.handle_error(ErrorMessage::InvalidNumber)
-        }
+        traced!(self, "parse_node_constant", {
+            match self.current_token() {
+                Token::IntegerLiteral(value) => {
+                    let span = self.current_span();
+                    self.advance();
+                    Ok(Expression::Literal { value, span })
+                },
+                _ => self.handle_error(ErrorMessage::InvalidNumber)
+            }
+        })
     }
-    fn parse_node_identifier(&mut self) -> Result<String, ErrorMessage>{
+    fn parse_node_identifier(&mut self) -> Result<(String, Span), ErrorMessage>{
         // <标识符>
-        match self.current_token() {
-            Token::Identifier(name) => {
-                self.advance();
-                Ok(name.clone())
-            },
-            _ => Err(ErrorMessage::ExpectedIdentifier),
-        }
+        traced!(self, "parse_node_identifier", {
+            match self.current_token() {
+                Token::Identifier(name) => {
+                    let span = self.current_span();
+                    self.advance();
+                    Ok((name, span))
+                },
+                _ => self.handle_error(ErrorMessage::ExpectedIdentifier),
+            }
+        })
     }
-}
\ No newline at end of file
+}
@@ -1,21 +1,306 @@
-use std::fs::File;
-use std::io::{Read};
+use crate::env::{Diagnostic, Diagnostics, ErrorMessage};
+use crate::lex::Span;
+use crate::options::Options;
+use encoding_rs::{Encoding, GBK, UTF_16BE, UTF_16LE, UTF_8};
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
 
-pub struct Preprocessor {
-    pub path: String, // 源程序名
-    pub content: String, // 源程序字符流
+// 预处理，处理 {$...} 形式的编译指令：
+// {$I 文件} 或 {$INCLUDE 文件} 原样插入另一个文件的内容
+// {$DEFINE X}、{$UNDEF X} 定义和取消符号，-D X 在命令行定义符号
+// {$IFDEF X}、{$IFNDEF X}、{$ELSE}、{$ENDIF} 按符号是否定义保留或删去其间的内容
+// 指令名和符号名不区分大小写；字符串常量和注释中的 {$ 不是指令
+// 删去的行保留为空行，预处理结果的每一行都在源映射中记下来自哪个文件的哪一行
+// 每个文件先按编码解码成UTF-8，去掉BOM，行尾统一为\n
+
+pub type Include<'a> = &'a dyn Fn(&str, &str) -> Option<(String, Vec<u8>)>; // 由文件名和包含它的文件求出路径和原始字节
+
+pub struct Decoded {
+    // 解码后的源文件
+    pub content: String, // 去掉BOM、行尾统一为\n的文本
+    pub encoding: &'static str, // 判断出的编码
+    pub invalid: Option<usize>, // 第一个无法解码的字节所在的行，这些字节换成了U+FFFD
+}
+
+pub fn decode(bytes: &[u8]) -> Decoded {
+    // 有BOM时按BOM解码，否则按内容猜测编码
+    let (encoding, bom) = Encoding::for_bom(bytes).unwrap_or_else(|| (guess(bytes), 0));
+    let body = &bytes[bom..];
+    let (text, invalid) = match encoding.decode_without_bom_handling_and_without_replacement(body) {
+        Some(text) => (text.into_owned(), None),
+        None => {
+            let text = encoding.decode_without_bom_handling(body).0.into_owned();
+            let at = text.find('\u{FFFD}').unwrap_or(0);
+            let line = text[..at].matches('\n').count() + 1;
+            (text, Some(line))
+        }
+    };
+    // Windows的\r\n和老式Mac的\r都换成\n，行号只按\n计算
+    Decoded {
+        content: text.replace("\r\n", "\n").replace('\r', "\n"),
+        encoding: encoding.name(),
+        invalid,
+    }
+}
+
+fn guess(bytes: &[u8]) -> &'static Encoding {
+    // 没有BOM的UTF-16按零字节的位置判断：ASCII字符的另一个字节是0
+    // 其余按UTF-8解码，不合法时再试GBK，Windows上的中文编辑器常用GBK保存
+    let zeros = |parity: usize| bytes.iter().skip(parity).step_by(2).filter(|&&b| b == 0).count() * 4 > bytes.len();
+    match (zeros(0), zeros(1)) {
+        (false, true) => UTF_16LE,
+        (true, false) => UTF_16BE,
+        _ if std::str::from_utf8(bytes).is_ok() => UTF_8,
+        _ if GBK.decode_without_bom_handling_and_without_replacement(bytes).is_some() => GBK,
+        _ => UTF_8,
+    }
+}
+
+pub fn load(paths: &[PathBuf], name: &str, from: &str) -> Option<(String, Vec<u8>)> {
+    // 在文件系统中找被包含的文件：先在包含它的文件所在的目录，再依次在搜索路径中查找
+    // 文件名没有后缀时补上.pas；返回规范化的路径，同一文件经不同的相对路径包含时才能发现循环包含，
+    // 在当前目录之下时写成相对于当前目录的路径
+    let name = match Path::new(name).extension() {
+        Some(_) => name.to_string(),
+        None => format!("{}.pas", name),
+    };
+    let dir = Path::new(from).parent().map(Path::to_path_buf).unwrap_or_default();
+    let cwd = std::env::current_dir().and_then(fs::canonicalize).ok();
+    std::iter::once(&dir).chain(paths).find_map(|d| {
+        let path = fs::canonicalize(d.join(&name)).ok()?;
+        let content = fs::read(&path).ok()?;
+        let path = cwd.as_ref().and_then(|c| path.strip_prefix(c).ok()).unwrap_or(&path);
+        Some((path.to_string_lossy().into_owned(), content))
+    })
 }
 
-impl Preprocessor {
-    pub fn new(name: &str) -> Self{
-        let mut p = Preprocessor{
-            path: name.to_string() + ".pas",
-            content: String::new(),
-        };
-        let mut input = File::open(&p.path).unwrap();
-        input.read_to_string(&mut p.content).unwrap();
-        println!("{}", p.content);
-        p
+#[derive(Clone, PartialEq, Debug, Default)]
+pub struct SourceMap {
+    pub files: Vec<String>, // 出现过的源文件，0号是主文件
+    pub lines: Vec<(usize, usize)>, // 预处理结果的每一行来自的文件号和行号
+}
+
+impl SourceMap {
+    pub fn locate(&self, line: usize) -> (usize, usize) {
+        // 预处理结果中的行对应的文件号和行号，文件结束之后的行接着主文件的最后一行往下数
+        match self.lines.get(line.wrapping_sub(1)) {
+            Some(&origin) => origin,
+            None if line == 0 => (0, 0),
+            None => {
+                let last = self.lines.iter().rev().find(|(f, _)| *f == 0).map_or(0, |&(_, l)| l);
+                (0, last + line - self.lines.len())
+            }
+        }
+    }
+    pub fn render(&self, diagnostic: &Diagnostic) -> String {
+        // 按原来的位置输出报错信息，包含进来的文件中的错误带上文件名
+        match self.locate(diagnostic.span.line) {
+            (0, line) => format!("LINE{}: {}", line, diagnostic.message),
+            (file, line) => format!("{}: LINE{}: {}", self.files[file], line, diagnostic.message),
+        }
     }
 }
 
+#[derive(Clone, PartialEq, Debug, Default)]
+pub struct Preprocessed {
+    // 预处理的结果
+    pub content: String, // 交给词法分析的源程序
+    pub encoding: &'static str, // 主文件的编码
+    pub map: SourceMap,
+    pub diagnostics: Diagnostics,
+}
+
+struct Condition {
+    // 一层条件编译
+    parent: bool, // 外层是否保留
+    taken: bool, // 条件是否成立
+    active: bool, // 当前分支是否保留
+    else_seen: bool, // 是否已经过了{$ELSE}
+    line: usize, // {$IFDEF}所在的行，未配对时在此报错
+}
+
+pub struct Preprocessor<'a> {
+    result: Preprocessed,
+    defines: HashSet<String>, // 已定义的符号，统一为大写
+    include: Include<'a>,
+    chain: Vec<usize>, // 正在展开的文件号，用于发现循环包含
+    conditions: Vec<Condition>,
+}
+
+impl<'a> Preprocessor<'a> {
+    pub fn new(options: &Options, include: Include<'a>) -> Self {
+        // 预处理只作用于内存中的源程序，读文件由调用者通过include负责
+        Preprocessor {
+            result: Preprocessed::default(),
+            defines: options.defines.iter().map(|d| d.to_uppercase()).collect(),
+            include,
+            chain: Vec::new(),
+            conditions: Vec::new(),
+        }
+    }
+    pub fn run(mut self, file: &str, source: &[u8]) -> Preprocessed {
+        self.result.map.files.push(file.to_string());
+        self.chain.push(0);
+        let ending = self.expand(0, source);
+        // 源程序最后没有换行时，结果也不加换行
+        if !ending {
+            self.result.content.pop();
+        }
+        self.result
+    }
+
+    fn active(&self) -> bool {
+        self.conditions.last().is_none_or(|c| c.active)
+    }
+    fn error(&mut self, message: ErrorMessage, line: usize) {
+        self.result.diagnostics.push(Diagnostic { message, span: Span { line, ..Span::default() } });
+    }
+    fn emit(&mut self, text: &str, origin: (usize, usize)) {
+        self.result.content.push_str(text);
+        self.result.content.push('\n');
+        self.result.map.lines.push(origin);
+    }
+    fn expand(&mut self, file: usize, source: &[u8]) -> bool {
+        // 逐行展开一个文件，跨行的注释要记住结束符；返回文件是否以换行结束
+        let decoded = decode(source);
+        if file == 0 {
+            self.result.encoding = decoded.encoding;
+        }
+        let source = decoded.content.as_str();
+        let depth = self.conditions.len();
+        let mut comment: Option<&str> = None;
+        for (i, line) in source.lines().enumerate() {
+            let origin = (file, i + 1);
+            let chars: Vec<char> = line.chars().collect();
+            let at = |j: usize, s: &str| s.chars().enumerate().all(|(k, c)| chars.get(j + k) == Some(&c));
+            let mut out = String::new();
+            let mut j = 0;
+            // 条件不成立的部分照常扫描，只是不输出，同一行中指令前后的文字按各自所处的条件取舍
+            let mut kept = 0; // out中已按条件取舍过的长度
+            let mut keep = self.active();
+            while j < chars.len() {
+                if !keep {
+                    out.truncate(kept);
+                }
+                kept = out.len();
+                keep = self.active();
+                if let Some(close) = comment {
+                    if at(j, close) {
+                        out.push_str(close);
+                        j += close.len();
+                        comment = None;
+                    } else {
+                        out.push(chars[j]);
+                        j += 1;
+                    }
+                    continue;
+                }
+                match chars[j] {
+                    '\'' => {
+                        // 字符串常量原样保留，'' 是两个相邻的字符串边界，不影响扫描
+                        let end = chars[j + 1..].iter().position(|&c| c == '\'').map_or(chars.len(), |k| j + k + 2);
+                        out.extend(&chars[j..end]);
+                        j = end;
+                    }
+                    '/' if at(j, "//") => {
+                        out.extend(&chars[j..]);
+                        j = chars.len();
+                    }
+                    '(' if at(j, "(*") => {
+                        out.push_str("(*");
+                        j += 2;
+                        comment = Some("*)");
+                    }
+                    '{' if at(j, "{$") && chars[j..].contains(&'}') => {
+                        let end = j + chars[j..].iter().position(|&c| c == '}').unwrap();
+                        let directive: String = chars[j + 2..end].iter().collect();
+                        j = end + 1;
+                        self.directive(&directive, depth, &mut out, origin);
+                    }
+                    '{' => {
+                        out.push('{');
+                        j += 1;
+                        comment = Some("}");
+                    }
+                    c => {
+                        out.push(c);
+                        j += 1;
+                    }
+                }
+            }
+            if !keep {
+                out.truncate(kept);
+            }
+            self.emit(&out, origin);
+        }
+        // 条件编译不能跨越文件的边界
+        while self.conditions.len() > depth {
+            let c = self.conditions.pop().unwrap();
+            self.error(ErrorMessage::UnterminatedConditional, c.line);
+        }
+        if let Some(line) = decoded.invalid {
+            let at = self.result.map.lines.iter().position(|&o| o == (file, line)).map_or(0, |i| i + 1);
+            self.error(ErrorMessage::InvalidEncoding, at);
+        }
+        source.ends_with('\n')
+    }
+    fn directive(&mut self, directive: &str, depth: usize, out: &mut String, origin: (usize, usize)) {
+        let (name, argument) = directive.split_once(char::is_whitespace).unwrap_or((directive, ""));
+        let argument = argument.trim();
+        let symbol = argument.to_uppercase();
+        let line = self.result.map.lines.len() + 1; // 指令所在的行在预处理结果中的行号
+        let nested = self.conditions.len() > depth; // 本文件中是否有未结束的条件编译
+        match name.to_uppercase().as_str() {
+            "I" | "INCLUDE" if self.active() => {
+                // 之前的内容单独成行，被包含文件之后的内容接着算作本行
+                if !out.trim().is_empty() {
+                    let text = std::mem::take(out);
+                    self.emit(&text, origin);
+                }
+                let name = argument.trim_matches('\'');
+                let current = self.result.map.files[origin.0].clone();
+                let Some((path, content)) = (self.include)(name, &current) else {
+                    return self.error(ErrorMessage::IncludeNotFound(name.to_string()), line);
+                };
+                if let Some(i) = self.chain.iter().position(|&f| self.result.map.files[f] == path) {
+                    let mut files: Vec<&str> = self.chain[i..].iter().map(|&f| self.result.map.files[f].as_str()).collect();
+                    files.push(&path);
+                    return self.error(ErrorMessage::CircularInclude(files.join(" -> ")), line);
+                }
+                self.result.map.files.push(path);
+                let file = self.result.map.files.len() - 1;
+                self.chain.push(file);
+                self.expand(file, &content);
+                self.chain.pop();
+            }
+            "DEFINE" if self.active() => {
+                self.defines.insert(symbol);
+            }
+            "UNDEF" if self.active() => {
+                self.defines.remove(&symbol);
+            }
+            "IFDEF" | "IFNDEF" => {
+                let taken = self.defines.contains(&symbol) == (name.eq_ignore_ascii_case("IFDEF"));
+                let parent = self.active();
+                self.conditions.push(Condition { parent, taken, active: parent && taken, else_seen: false, line });
+            }
+            "ELSE" => match self.conditions.last_mut().filter(|_| nested) {
+                Some(c) if !c.else_seen => {
+                    c.active = c.parent && !c.taken;
+                    c.else_seen = true;
+                }
+                _ => self.error(ErrorMessage::UnmatchedDirective("ELSE".to_string()), line),
+            },
+            "ENDIF" => match nested {
+                true => {
+                    self.conditions.pop();
+                }
+                false => self.error(ErrorMessage::UnmatchedDirective("ENDIF".to_string()), line),
+            },
+            "I" | "INCLUDE" | "DEFINE" | "UNDEF" => (),
+            _ if self.active() => self.error(ErrorMessage::UnknownDirective(name.to_string()), line),
+            _ => (),
+        }
+    }
+}
@@ -0,0 +1,49 @@
+use crate::env::ErrorMessage;
+
+// 拼写建议：写错的关键字或未声明的名字与某个候选名字足够接近时，在报错信息后附上 did you mean
+// 按编辑距离比较，不区分大小写；单个字符的名字不给建议，距离不超过名字长度的三分之一（至少为1）
+
+pub fn edit_distance(a: &str, b: &str) -> usize {
+    // 插入、删除、替换一个字符以及交换相邻的两个字符各算一步
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    let mut d = vec![vec![0; b.len() + 1]; a.len() + 1];
+    d[0] = (0..=b.len()).collect();
+    for (i, row) in d.iter_mut().enumerate() {
+        row[0] = i;
+    }
+    for i in 1..=a.len() {
+        for j in 1..=b.len() {
+            let cost = (a[i - 1] != b[j - 1]) as usize;
+            d[i][j] = (d[i - 1][j] + 1).min(d[i][j - 1] + 1).min(d[i - 1][j - 1] + cost);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                d[i][j] = d[i][j].min(d[i - 2][j - 2] + 1);
+            }
+        }
+    }
+    d[a.len()][b.len()]
+}
+
+pub fn suggest<'a>(name: &str, candidates: impl IntoIterator<Item = &'a str>) -> Option<&'a str> {
+    // 在候选名字中找距离最小的一个，距离相同时取字典序最小的，保证结果确定
+    // 只差大小写的候选距离为0，同样给出建议
+    let len = name.chars().count();
+    if len < 2 {
+        return None;
+    }
+    let name = name.to_lowercase();
+    candidates
+        .into_iter()
+        .map(|c| (edit_distance(&name, &c.to_lowercase()), c))
+        .filter(|&(d, _)| d <= (len / 3).max(1))
+        .min()
+        .map(|(_, c)| c)
+}
+
+pub fn with_suggestion(message: ErrorMessage, suggestion: Option<&str>) -> ErrorMessage {
+    // 有建议时把报错信息包装成DidYouMean
+    match suggestion {
+        Some(s) => ErrorMessage::DidYouMean(Box::new(message), s.to_string()),
+        None => message,
+    }
+}
@@ -0,0 +1,150 @@
+use crate::env::Env;
+use std::fmt;
+
+// 类型的表示，说明语句、符号表和语义检查共用
+
+pub const MAX_SIZE: i64 = i32::MAX as i64; // 类型和活动记录的最大单元数，指令中的偏移是i32
+
+#[derive(Clone, PartialEq, Debug)]
+pub enum Type {
+    Integer,
+    Boolean,
+    String, // 字符串常量的类型，只能出现在写语句中，不能声明变量
+    Subrange {
+        // integer的子界，与integer相容，赋值时检查是否越界
+        low: i64,
+        high: i64,
+    },
+    Array {
+        // 多维数组看作数组的数组
+        low: i64, // 下界
+        high: i64, // 上界
+        element: Box<Type>, // 元素类型
+    },
+    Record {
+        fields: Vec<(String, Type)>, // 按说明的顺序排列的域名和域类型
+    },
+}
+
+impl Type {
+    pub fn size(&self) -> i64 {
+        // 该类型的值在运行栈中占用的单元数，过大的类型已在语义检查时报错，按i64::MAX计
+        self.checked_size().unwrap_or(i64::MAX)
+    }
+    pub fn checked_size(&self) -> Option<i64> {
+        // 同size，单元数超出i64的范围时返回None
+        match self {
+            Type::Integer | Type::Boolean => Some(1),
+            Type::String => Some(0), // 字符串常量存放在常量池中，不占运行栈
+            Type::Subrange { .. } => Some(1),
+            Type::Array { low, high, element } => high.checked_sub(*low)?.checked_add(1)?.checked_mul(element.checked_size()?),
+            Type::Record { fields } => fields.iter().try_fold(0i64, |n, (_, t)| n.checked_add(t.checked_size()?)),
+        }
+    }
+    pub fn too_large(&self) -> bool {
+        // 单元数是否超过MAX_SIZE
+        self.checked_size().is_none_or(|n| n > MAX_SIZE)
+    }
+    pub fn is_simple(&self) -> bool {
+        // 是否为可以直接比较、读写的简单类型
+        matches!(self, Type::Integer | Type::Boolean | Type::Subrange { .. })
+    }
+    pub fn field(&self, name: &str, env: &Env) -> Option<(i64, &Type)> {
+        // 查记录的域，返回域相对于记录起始的偏移和域的类型，域名按符号表的规则比较
+        let Type::Record { fields } = self else {
+            return None;
+        };
+        let mut offset = 0;
+        for (n, t) in fields {
+            if env.same(n, name) {
+                return Some((offset, t));
+            }
+            offset += t.size();
+        }
+        None
+    }
+    pub fn accepts(&self, other: &Type) -> bool {
+        // other类型的值能否用在需要self类型的地方
+        // 子界与integer相容，其余类型按结构相等
+        let integral = |t: &Type| matches!(t, Type::Integer | Type::Subrange { .. });
+        self == other || (integral(self) && integral(other))
+    }
+}
+
+impl fmt::Display for Type {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Type::Integer => write!(f, "integer"),
+            Type::Boolean => write!(f, "boolean"),
+            Type::String => write!(f, "string"),
+            Type::Subrange { low, high } => write!(f, "{}..{}", low, high),
+            Type::Array { low, high, element } => write!(f, "array[{}..{}] of {}", low, high, element),
+            Type::Record { fields } => {
+                write!(f, "record")?;
+                for (i, (name, t)) in fields.iter().enumerate() {
+                    let separator = if i == 0 { " " } else { "; " };
+                    write!(f, "{}{} {}", separator, t, name)?;
+                }
+                write!(f, " end")
+            }
+        }
+    }
+}
+
+impl std::str::FromStr for Type {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        // 从Display的输出读回类型，供单元的接口文件使用
+        match parse_type(s.trim()) {
+            Some((t, "")) => Ok(t),
+            _ => Err(format!("invalid type '{}'", s)),
+        }
+    }
+}
+
+fn parse_type(s: &str) -> Option<(Type, &str)> {
+    // 读一个类型，返回类型和剩余的输入
+    if let Some(rest) = s.strip_prefix("integer") {
+        return Some((Type::Integer, rest));
+    }
+    if let Some(rest) = s.strip_prefix("boolean") {
+        return Some((Type::Boolean, rest));
+    }
+    if let Some(rest) = s.strip_prefix("string") {
+        return Some((Type::String, rest));
+    }
+    if let Some(rest) = s.strip_prefix("array[") {
+        let (low, high, rest) = parse_range(rest)?;
+        let (element, rest) = parse_type(rest.strip_prefix("] of ")?)?;
+        return Some((Type::Array { low, high, element: Box::new(element) }, rest));
+    }
+    if let Some(mut rest) = s.strip_prefix("record") {
+        // 域之间以分号分隔，域的类型在前，域名在后
+        let mut fields = Vec::new();
+        loop {
+            if let Some(r) = rest.strip_prefix(" end") {
+                return Some((Type::Record { fields }, r));
+            }
+            let separator = if fields.is_empty() { " " } else { "; " };
+            let (t, r) = parse_type(rest.strip_prefix(separator)?)?;
+            let r = r.strip_prefix(' ')?;
+            let len = r.find([';', ' ']).unwrap_or(r.len());
+            fields.push((r[..len].to_string(), t));
+            rest = &r[len..];
+        }
+    }
+    let (low, high, rest) = parse_range(s)?;
+    Some((Type::Subrange { low, high }, rest))
+}
+
+fn parse_range(s: &str) -> Option<(i64, i64, &str)> {
+    // 读 下界..上界
+    let number = |s: &str| -> Option<(i64, usize)> {
+        let len = s.char_indices().find(|&(i, c)| !(c.is_ascii_digit() || (i == 0 && c == '-'))).map_or(s.len(), |(i, _)| i);
+        Some((s[..len].parse().ok()?, len))
+    };
+    let (low, n) = number(s)?;
+    let rest = s[n..].strip_prefix("..")?;
+    let (high, m) = number(rest)?;
+    Some((low, high, &rest[m..]))
+}
@@ -0,0 +1,366 @@
+use crate::code::{Instruction, Program};
+use crate::env::{ConstantItem, Diagnostic, Diagnostics, Env, ErrorMessage, ProcedureItem, TypeItem};
+use crate::lex::Span;
+use crate::options::Options;
+use crate::types::Type;
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+// 单元：分别编译的模块
+// 编译一个单元得到它的接口文件(.itf)，其中有接口部分说明的常量、类型、过程表项，以及单元的目标代码，
+// 引用该单元的程序只读接口文件，不再编译单元的源程序；连接时把各单元的代码接在程序的代码之后
+// 接口文件中记有单元源程序的指纹，以及编译时所引用单元导出符号的指纹，
+// 源程序的内容变了，或者引用的单元导出的符号变了才重新编译，与文件的修改时间无关
+// 模块解析需要在搜索路径中查找和读写文件，不属于lib.rs中的纯函数接口
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct Interface {
+    // 一个单元编译后的结果
+    pub name: String, // 单元名
+    pub source: u64, // 单元源程序的指纹，见fingerprint
+    pub uses: Vec<(String, u64)>, // 该单元引用的单元及编译时其导出符号的指纹，连接时一并接入
+    pub constants: Vec<ConstantItem>, // 导出的常量
+    pub types: Vec<TypeItem>, // 导出的类型
+    pub procedures: Vec<ProcedureItem>, // 导出的过程，padr是入口在单元代码中的位置
+    pub program: Program, // 单元的目标代码，调用其他单元的过程处记在externals中
+}
+
+impl Interface {
+    pub fn import(&self, env: &mut Env) -> Vec<String> {
+        // 把导出的符号登记到当前作用域，返回已有同名符号而没有登记的名字
+        // 过程的状态记为2，调用处由连接程序回填入口
+        let mut clashes = Vec::new();
+        for c in &self.constants {
+            match env.check_repeat(c.cname.clone()) {
+                true => clashes.push(c.cname.clone()),
+                false => env.add_constant(c.cname.clone(), c.ctype.clone(), c.cvalue),
+            }
+        }
+        for t in &self.types {
+            match env.check_repeat(t.tname.clone()) {
+                true => clashes.push(t.tname.clone()),
+                false => env.add_type(t.tname.clone(), t.ttype.clone()),
+            }
+        }
+        for p in &self.procedures {
+            if env.check_repeat(p.pname.clone()) {
+                clashes.push(p.pname.clone());
+                continue;
+            }
+            env.add_procedure(p.pname.clone(), p.preturn.clone(), p.ptype.clone(), p.pkind.clone());
+            if let Some(item) = env.find_procedure_mut(&p.pname) {
+                item.padr = p.padr;
+                item.pstate = 2;
+            }
+        }
+        clashes
+    }
+    pub fn procedure(&self, name: &str) -> Option<&ProcedureItem> {
+        self.procedures.iter().find(|p| p.pname == name)
+    }
+    pub fn exports(&self) -> u64 {
+        // 导出符号的指纹：常量、类型和过程的首部，不含过程入口和代码，引用它的单元据此判断是否过时
+        let mut text = String::new();
+        for c in &self.constants {
+            text += &format!("const\t{}\t{}\t{}\n", c.cname, c.ctype, c.cvalue);
+        }
+        for t in &self.types {
+            text += &format!("type\t{}\t{}\n", t.tname, t.ttype);
+        }
+        for p in &self.procedures {
+            let preturn = p.preturn.as_ref().map_or("-".to_string(), |t| t.to_string());
+            text += &format!("procedure\t{}\t{}", p.pname, preturn);
+            for (kind, t) in p.pkind.iter().zip(&p.ptype) {
+                text += &format!("\t{}:{}", kind, t);
+            }
+            text.push('\n');
+        }
+        fingerprint(text.as_bytes())
+    }
+}
+
+impl fmt::Display for Interface {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        // 接口文件每行一项，各字段以制表符分隔，类型和指令按其输出形式书写
+        writeln!(f, "unit\t{}", self.name)?;
+        writeln!(f, "source\t{:016x}", self.source)?;
+        for (u, exports) in &self.uses {
+            writeln!(f, "uses\t{}\t{:016x}", u, exports)?;
+        }
+        for c in &self.constants {
+            writeln!(f, "const\t{}\t{}\t{}", c.cname, c.ctype, c.cvalue)?;
+        }
+        for t in &self.types {
+            writeln!(f, "type\t{}\t{}", t.tname, t.ttype)?;
+        }
+        for p in &self.procedures {
+            let preturn = p.preturn.as_ref().map_or("-".to_string(), |t| t.to_string());
+            write!(f, "procedure\t{}\t{}\t{}", p.pname, p.padr, preturn)?;
+            for (kind, t) in p.pkind.iter().zip(&p.ptype) {
+                write!(f, "\t{}:{}", kind, t)?;
+            }
+            writeln!(f)?;
+        }
+        for (ins, span) in self.program.code.iter().zip(&self.program.spans) {
+            writeln!(f, "code\t{}\t{:?}", span.line, ins)?;
+        }
+        for s in &self.program.strings {
+            writeln!(f, "string\t{:?}", s)?;
+        }
+        for (at, unit, name) in &self.program.externals {
+            writeln!(f, "external\t{}\t{}\t{}", at, unit, name)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::str::FromStr for Interface {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        // 读回接口文件，任何一行格式不对都整个作废
+        let mut unit = Interface {
+            name: String::new(),
+            source: 0,
+            uses: Vec::new(),
+            constants: Vec::new(),
+            types: Vec::new(),
+            procedures: Vec::new(),
+            program: Program::default(),
+        };
+        for (i, line) in s.lines().enumerate() {
+            let error = || format!("line {}: '{}'", i + 1, line);
+            let fields: Vec<&str> = line.split('\t').collect();
+            let number = |i: usize| -> Result<i64, String> { fields.get(i).and_then(|f| f.parse().ok()).ok_or_else(error) };
+            match fields[..] {
+                ["unit", name] => unit.name = name.to_string(),
+                ["source", hash] => unit.source = u64::from_str_radix(hash, 16).map_err(|_| error())?,
+                ["uses", name, exports] => {
+                    unit.uses.push((name.to_string(), u64::from_str_radix(exports, 16).map_err(|_| error())?));
+                }
+                ["const", name, ctype, _] => {
+                    unit.constants.push(ConstantItem::new(name.to_string(), 0, ctype.parse()?, number(3)?));
+                }
+                ["type", name, ttype] => unit.types.push(TypeItem::new(name.to_string(), 0, ttype.parse()?)),
+                ["procedure", name, _, preturn, ref parameters @ ..] => {
+                    let preturn = match preturn {
+                        "-" => None,
+                        t => Some(t.parse()?),
+                    };
+                    let mut ptype = Vec::new();
+                    let mut pkind = Vec::new();
+                    for p in parameters {
+                        let (kind, t) = p.split_once(':').ok_or_else(error)?;
+                        pkind.push(kind.parse().map_err(|_| error())?);
+                        ptype.push(t.parse::<Type>()?);
+                    }
+                    let mut item = ProcedureItem::new(name.to_string(), 0, preturn, ptype, pkind);
+                    item.padr = number(2)? as usize;
+                    unit.procedures.push(item);
+                }
+                ["code", _, ins] => {
+                    unit.program.code.push(ins.parse()?);
+                    unit.program.spans.push(Span { line: number(1)? as usize, ..Span::default() });
+                }
+                ["string", value] => unit.program.strings.push(unescape(value).ok_or_else(error)?),
+                ["external", _, u, name] => unit.program.externals.push((number(1)? as usize, u.to_string(), name.to_string())),
+                [""] => (),
+                _ => return Err(error()),
+            }
+        }
+        match unit.name.is_empty() {
+            true => Err("missing unit name".to_string()),
+            false => Ok(unit),
+        }
+    }
+}
+
+pub fn fingerprint(source: &[u8]) -> u64 {
+    // 源程序原始字节的FNV-1a散列，写入接口文件，用来判断接口文件是否过时
+    source.iter().fold(0xcbf29ce484222325, |h, &b| (h ^ b as u64).wrapping_mul(0x100000001b3))
+}
+
+fn unescape(s: &str) -> Option<String> {
+    // 读回按Debug格式输出的字符串
+    let mut chars = s.strip_prefix('"')?.strip_suffix('"')?.chars();
+    let mut out = String::new();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+        match chars.next()? {
+            'n' => out.push('\n'),
+            'r' => out.push('\r'),
+            't' => out.push('\t'),
+            '0' => out.push('\0'),
+            'u' => {
+                let code: String = chars.by_ref().skip(1).take_while(|&c| c != '}').collect();
+                out.push(char::from_u32(u32::from_str_radix(&code, 16).ok()?)?);
+            }
+            c => out.push(c),
+        }
+    }
+    Some(out)
+}
+
+pub fn link(mut program: Program, units: &[Interface]) -> Result<Program, Diagnostic> {
+    // 把各单元的代码依次接在程序之后，跳转和调用的目标、字符串常量的下标加上所在位置，再回填对其他单元的调用
+    let mut bases = HashMap::new();
+    for unit in units {
+        let (base, strings) = (program.code.len(), program.strings.len());
+        bases.insert(unit.name.as_str(), base);
+        for ins in &unit.program.code {
+            program.code.push(match *ins {
+                Instruction::Jmp(a) => Instruction::Jmp(a + base),
+                Instruction::Jpc(a) => Instruction::Jpc(a + base),
+                Instruction::Jtb(low, high, a) => Instruction::Jtb(low, high, a + base),
+                Instruction::Cal(level, a) => Instruction::Cal(level, a + base),
+                Instruction::Wrs(i) => Instruction::Wrs(i + strings),
+                ins => ins,
+            });
+        }
+        program.spans.extend(&unit.program.spans);
+        program.strings.extend(unit.program.strings.iter().cloned());
+        program.externals.extend(unit.program.externals.iter().map(|(at, u, n)| (at + base, u.clone(), n.clone())));
+    }
+    let error = |message| Diagnostic { message, span: Span::default() };
+    for (at, unit, name) in std::mem::take(&mut program.externals) {
+        let base = bases.get(unit.as_str()).ok_or_else(|| error(ErrorMessage::UnitNotFound(unit.clone())))?;
+        let entry = units
+            .iter()
+            .find(|u| u.name == unit)
+            .and_then(|u| u.procedure(&name))
+            .ok_or_else(|| error(ErrorMessage::UndeclaredIdentifier(name.clone())))?
+            .padr;
+        if let Instruction::Cal(level, _) = program.code[at] {
+            program.code[at] = Instruction::Cal(level, base + entry);
+        }
+    }
+    Ok(program)
+}
+
+pub struct Resolver {
+    // 模块解析：按引用说明在搜索路径中查找单元，必要时编译单元的源程序并写出接口文件
+    paths: Vec<PathBuf>, // 搜索路径，依次查找
+    options: Options,
+    units: Vec<Interface>, // 已经解析的单元，被引用的单元排在前面
+    active: Vec<String>, // 正在解析的单元链，用于发现循环引用
+    failed: Vec<String>, // 编译出错的单元，再次引用时不重复编译
+    pub failures: Vec<(PathBuf, Vec<String>)>, // 编译出错的单元的源程序和按源映射输出的报错信息
+}
+
+impl Resolver {
+    pub fn new(paths: Vec<PathBuf>, options: &Options) -> Self {
+        Resolver {
+            paths,
+            options: options.clone(),
+            units: Vec::new(),
+            active: Vec::new(),
+            failed: Vec::new(),
+            failures: Vec::new(),
+        }
+    }
+    pub fn units(&self) -> &[Interface] {
+        &self.units
+    }
+    pub fn resolve(&mut self, owner: Option<&str>, uses: &[(String, Span)]) -> Diagnostics {
+        // 解析owner引用的各单元，owner是正在编译的单元名，程序为None；报错位置是引用说明中的单元名
+        let mut diagnostics = Vec::new();
+        if let Some(owner) = owner {
+            self.active.push(owner.to_string());
+        }
+        for (name, span) in uses {
+            if let Err(message) = self.resolve_unit(name) {
+                diagnostics.push(Diagnostic { message, span: *span });
+            }
+        }
+        if owner.is_some() {
+            self.active.pop();
+        }
+        diagnostics
+    }
+    fn resolve_unit(&mut self, name: &str) -> Result<(), ErrorMessage> {
+        // 单元按文件名查找，所以单元名在不区分大小写的模式下也区分大小写
+        if let Some(i) = self.active.iter().position(|a| a == name) {
+            let chain = [&self.active[i..], &[name.to_string()]].concat().join(" -> ");
+            return Err(ErrorMessage::CircularUnit(chain));
+        }
+        if self.units.iter().any(|u| u.name == name) {
+            return Ok(());
+        }
+        if self.failed.iter().any(|f| f == name) {
+            return Err(ErrorMessage::UnitHasErrors(name.to_string()));
+        }
+        let (source, interface) = self.find(name).ok_or_else(|| ErrorMessage::UnitNotFound(name.to_string()))?;
+        // 接口文件与源程序的指纹相符（或者只有接口文件），且引用的单元导出的符号与编译时相同，才直接使用
+        // 否则重新编译；没有源程序可以重新编译时，接口文件作废
+        let text = fs::read(&source).ok();
+        let cached = fs::read_to_string(&interface)
+            .ok()
+            .and_then(|t| t.parse::<Interface>().ok())
+            .filter(|u| u.name == name && text.as_ref().is_none_or(|t| u.source == fingerprint(t)));
+        if let Some(unit) = cached {
+            let uses: Vec<(String, Span)> = unit.uses.iter().map(|(u, _)| (u.clone(), Span::default())).collect();
+            if let Some(d) = self.resolve(Some(name), &uses).into_iter().next() {
+                return Err(d.message);
+            }
+            let current = unit.uses.iter().all(|(u, exports)| self.units.iter().any(|i| &i.name == u && i.exports() == *exports));
+            if current {
+                self.units.push(unit);
+                return Ok(());
+            }
+        }
+        let unit = match text {
+            Some(_) => self.compile(name, &source, &interface)?,
+            None => return Err(ErrorMessage::InvalidInterfaceFile(interface.display().to_string())),
+        };
+        self.units.push(unit);
+        Ok(())
+    }
+    fn find(&self, name: &str) -> Option<(PathBuf, PathBuf)> {
+        // 在搜索路径中找单元的源程序或接口文件
+        self.paths.iter().find_map(|dir| {
+            let source = dir.join(format!("{}.pas", name));
+            let interface = dir.join(format!("{}.itf", name));
+            (source.exists() || interface.exists()).then_some((source, interface))
+        })
+    }
+    fn compile(&mut self, name: &str, source: &Path, interface: &Path) -> Result<Interface, ErrorMessage> {
+        // 编译单元的源程序，出错时把报错信息记在failures中
+        let text = fs::read(source).map_err(|_| ErrorMessage::UnitNotFound(name.to_string()))?;
+        // 单元中包含的文件同样先在单元所在的目录，再在搜索路径中查找
+        let paths = self.paths.clone();
+        let file = source.to_string_lossy();
+        let prep = crate::preprocess_with(&text, &file, &self.options, &|n, f| crate::load(&paths, n, f));
+        let tokens = crate::lex_with(&prep.content, &self.options);
+        let mut diagnostics = prep.diagnostics.clone();
+        diagnostics.extend(tokens.diagnostics.clone());
+        let ast = match crate::parse_with(tokens, &self.options) {
+            Ok(ast) => ast,
+            Err(errors) => {
+                diagnostics.extend(errors);
+                self.failed.push(name.to_string());
+                self.failures.push((source.to_path_buf(), diagnostics.iter().map(|d| prep.map.render(d)).collect()));
+                return Err(ErrorMessage::UnitHasErrors(name.to_string()));
+            }
+        };
+        if ast.unit.as_ref().is_none_or(|u| u.name != name) {
+            return Err(ErrorMessage::NotAUnit(name.to_string()));
+        }
+        diagnostics.extend(self.resolve(Some(name), &ast.uses));
+        if diagnostics.is_empty() {
+            diagnostics.extend(crate::check_with_units(&ast, &self.units, &self.options));
+        }
+        if !diagnostics.is_empty() {
+            self.failed.push(name.to_string());
+            self.failures.push((source.to_path_buf(), diagnostics.iter().map(|d| prep.map.render(d)).collect()));
+            return Err(ErrorMessage::UnitHasErrors(name.to_string()));
+        }
+        let mut unit = crate::generate_unit(&ast, &self.units, &self.options);
+        unit.source = fingerprint(&text);
+        fs::write(interface, unit.to_string()).map_err(|_| ErrorMessage::InvalidInterfaceFile(interface.display().to_string()))?;
+        Ok(unit)
+    }
+}
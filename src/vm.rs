@@ -0,0 +1,225 @@
+use crate::env::{ErrorMessage, Diagnostic};
+use crate::code::{Instruction, Program, RESULT};
+use std::io::{BufRead, Write};
+
+const STACK_LIMIT: usize = 1 << 20; // 运行栈的最大深度
+
+pub struct Machine<'a> {
+    // 栈式虚拟机，解释执行目标代码
+    program: &'a Program,
+    stack: Vec<i64>, // 运行栈
+    pc: usize, // 下一条指令的地址
+    bp: usize, // 当前活动记录的基址
+    input: &'a mut dyn BufRead, // read语句的输入
+    output: &'a mut dyn Write, // write语句的输出
+    pending: Vec<String>, // 已读入但尚未使用的输入项，逆序存放
+    maxint: i64, // integer的最大值，运算结果和读入的值超出范围时报整数溢出
+}
+
+impl<'a> Machine<'a> {
+    pub fn new(program: &'a Program, maxint: i64, input: &'a mut dyn BufRead, output: &'a mut dyn Write) -> Self {
+        Machine {
+            program,
+            stack: Vec::new(),
+            pc: 0,
+            bp: 0,
+            input,
+            output,
+            pending: Vec::new(),
+            maxint,
+        }
+    }
+    pub fn run(mut self) -> Result<(), Diagnostic> {
+        // 逐条执行指令直到停机，出错时返回出错指令对应的源程序位置
+        loop {
+            let at = self.pc;
+            let ins = self.program.code[at];
+            self.pc += 1;
+            let res = match ins {
+                Instruction::Hlt => return Ok(()),
+                _ => self.step(ins),
+            };
+            if let Err(message) = res {
+                return Err(Diagnostic { message, span: self.program.spans[at] });
+            }
+        }
+    }
+
+    fn step(&mut self, ins: Instruction) -> Result<(), ErrorMessage> {
+        match ins {
+            Instruction::Lit(v) => self.push(v)?,
+            Instruction::Lod(level, offset) => {
+                let a = self.range(self.address(level, offset), 1)?.start;
+                self.push(self.stack[a])?;
+            }
+            Instruction::Sto(level, offset) => {
+                let v = self.pop();
+                let a = self.range(self.address(level, offset), 1)?.start;
+                self.stack[a] = v;
+            }
+            Instruction::Lda(level, offset) => {
+                let a = self.address(level, offset);
+                self.push(a)?;
+            }
+            Instruction::Ldi(size) => {
+                let a = self.pop();
+                let range = self.range(a, size)?;
+                for i in range {
+                    self.push(self.stack[i])?;
+                }
+            }
+            Instruction::Sti(size) => {
+                let a = self.pop();
+                let range = self.range(a, size)?;
+                for i in range.rev() {
+                    self.stack[i] = self.pop();
+                }
+            }
+            Instruction::Idx(low, size) => {
+                // 关闭下标检查时下标可以是任意值，地址溢出按越界访问报错
+                let i = self.pop();
+                let a = self.pop();
+                let address = i.checked_sub(low).and_then(|d| d.checked_mul(size)).and_then(|d| a.checked_add(d));
+                self.push(address.ok_or(ErrorMessage::AddressOutOfRange(a))?)?;
+            }
+            Instruction::Chk(low, high) => {
+                let i = *self.stack.last().expect("运行栈为空");
+                if i < low || i > high {
+                    return Err(ErrorMessage::IndexOutOfBounds(i, low, high));
+                }
+            }
+            Instruction::Rng(low, high) => {
+                let v = *self.stack.last().expect("运行栈为空");
+                if v < low || v > high {
+                    return Err(ErrorMessage::ValueOutOfRange(v, low, high));
+                }
+            }
+            Instruction::Opr(operator) => {
+                let b = self.pop();
+                let a = self.pop();
+                let v = operator.apply(a, b).ok_or(match b {
+                    0 => ErrorMessage::DivisionByZero,
+                    _ => ErrorMessage::IntegerOverflow,
+                })?;
+                self.push(self.fit(v)?)?;
+            }
+            Instruction::Neg => {
+                let v = self.pop();
+                let v = v.checked_neg().ok_or(ErrorMessage::IntegerOverflow)?;
+                self.push(self.fit(v)?)?;
+            }
+            Instruction::Not => {
+                let v = self.pop();
+                self.push((v == 0) as i64)?;
+            }
+            Instruction::Jmp(target) => self.pc = target,
+            Instruction::Jpc(target) => {
+                if self.pop() == 0 {
+                    self.pc = target;
+                }
+            }
+            Instruction::Jtb(low, high, default) => {
+                // 其后紧跟跳转表，每个值对应一条跳转指令
+                let v = self.pop();
+                match v < low || v > high {
+                    true => self.pc = default,
+                    false => self.pc += (v - low) as usize,
+                }
+            }
+            Instruction::Cal(level, entry) => {
+                // 压入静态链、动态链和返回地址，建立新的活动记录
+                let sl = self.base(level);
+                let bp = self.stack.len();
+                self.push(sl as i64)?;
+                self.push(self.bp as i64)?;
+                self.push(self.pc as i64)?;
+                self.bp = bp;
+                self.pc = entry;
+            }
+            Instruction::Ent(size) => {
+                for _ in 0..size {
+                    self.push(0)?;
+                }
+            }
+            Instruction::Ret(parameters, function) => {
+                // 撤销活动记录和实参，函数结果留在栈顶
+                let result = self.stack[self.bp + RESULT as usize];
+                let bp = self.bp;
+                self.pc = self.stack[bp + 2] as usize;
+                self.bp = self.stack[bp + 1] as usize;
+                self.stack.truncate(bp - parameters as usize);
+                if function {
+                    self.push(result)?;
+                }
+            }
+            Instruction::Red => {
+                let v = self.read()?;
+                self.push(self.fit(v)?)?;
+            }
+            Instruction::Wrt(boolean) => {
+                let v = self.pop();
+                // 输出流关闭时没有别的地方可以报告，忽略写入错误
+                let _ = match boolean {
+                    true => write!(self.output, "{}", v != 0),
+                    false => write!(self.output, "{}", v),
+                };
+            }
+            Instruction::Wrs(index) => {
+                let _ = write!(self.output, "{}", self.program.strings[index]);
+            }
+            Instruction::Wln => {
+                let _ = writeln!(self.output);
+            }
+            Instruction::Hlt => (),
+        }
+        Ok(())
+    }
+    fn push(&mut self, v: i64) -> Result<(), ErrorMessage> {
+        if self.stack.len() >= STACK_LIMIT {
+            return Err(ErrorMessage::StackOverflow);
+        }
+        self.stack.push(v);
+        Ok(())
+    }
+    fn pop(&mut self) -> i64 {
+        self.stack.pop().expect("运行栈为空")
+    }
+    fn base(&self, level: i32) -> usize {
+        // 沿静态链向外走level层，得到定义所在过程的活动记录基址
+        let mut b = self.bp;
+        for _ in 0..level {
+            b = self.stack[b] as usize;
+        }
+        b
+    }
+    fn address(&self, level: i32, offset: i32) -> i64 {
+        self.base(level) as i64 + offset as i64
+    }
+    fn range(&self, a: i64, size: i32) -> Result<std::ops::Range<usize>, ErrorMessage> {
+        // 从地址a起的size个单元，关闭下标检查时地址可能落在运行栈之外
+        let end = a + size as i64;
+        match a >= 0 && end <= self.stack.len() as i64 {
+            true => Ok(a as usize..end as usize),
+            false => Err(ErrorMessage::AddressOutOfRange(a)),
+        }
+    }
+    fn fit(&self, v: i64) -> Result<i64, ErrorMessage> {
+        // 检查值是否在integer的范围内
+        match (-self.maxint - 1..=self.maxint).contains(&v) {
+            true => Ok(v),
+            false => Err(ErrorMessage::IntegerOverflow),
+        }
+    }
+    fn read(&mut self) -> Result<i64, ErrorMessage> {
+        // 输入项以空白分隔，按需逐行读入
+        while self.pending.is_empty() {
+            let mut line = String::new();
+            match self.input.read_line(&mut line) {
+                Ok(0) | Err(_) => return Err(ErrorMessage::InvalidInput),
+                Ok(_) => self.pending = line.split_whitespace().rev().map(String::from).collect(),
+            }
+        }
+        let item = self.pending.pop().unwrap_or_default();
+        item.parse().map_err(|_| ErrorMessage::InvalidInput)
+    }
+}
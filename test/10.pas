@@ -0,0 +1,35 @@
+begin
+  integer x, y, n ;
+  procedure swap(var a, var b) ;
+    begin
+      integer a, b, t ;
+      t:=a ;
+      a:=b ;
+      b:=t
+    end ;
+  procedure order(var a, var b) ;
+    begin
+      integer a, b ;
+      if a > b then swap(a, b)
+    end ;
+  procedure count(var c) ;
+    begin
+      integer c ;
+      c:=c+1
+    end ;
+  procedure hello ;
+    begin
+      integer h ;
+      h:=42 ;
+      writeln(h)
+    end ;
+  read(x) ;
+  read(y) ;
+  order(x, y) ;
+  writeln(x) ;
+  writeln(y) ;
+  n:=0 ;
+  while n < 3 do count(n) ;
+  writeln(n) ;
+  hello ;
+end
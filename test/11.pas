@@ -0,0 +1,56 @@
+begin
+  array[1..5] of integer a, b ;
+  array[0..2, 1..3] of integer m ;
+  integer i, j, s ;
+  integer function sum(v, n) ;
+    begin
+      array[1..5] of integer v ;
+      integer n, k, t ;
+      t:=0 ;
+      k:=1 ;
+      while k <= n do
+        begin
+          t:=t+v[k] ;
+          v[k]:=0 ;
+          k:=k+1
+        end ;
+      sum:=t
+    end ;
+  procedure double(var v) ;
+    begin
+      array[1..5] of integer v ;
+      integer k ;
+      k:=1 ;
+      repeat
+        v[k]:=v[k]*2 ;
+        k:=k+1
+      until k > 5
+    end ;
+  a[1]:=1 ;
+  i:=2 ;
+  while i <= 5 do
+    begin
+      a[i]:=a[i-1]*2 ;
+      i:=i+1
+    end ;
+  s:=sum(a, 5) ;
+  writeln(s) ;
+  writeln(a[5]) ;
+  b:=a ;
+  double(b) ;
+  writeln(b[5]) ;
+  i:=0 ;
+  while i <= 2 do
+    begin
+      j:=1 ;
+      while j <= 3 do
+        begin
+          m[i, j]:=i*10+j ;
+          j:=j+1
+        end ;
+      i:=i+1
+    end ;
+  writeln(m[2][3]) ;
+  read(i) ;
+  writeln(a[i])
+end
@@ -0,0 +1,21 @@
+begin
+  integer m, k, a, b, t ;
+  boolean big ;
+  writeln('Fibonacci numbers') ;
+  writeln ;
+  m:=1 ;
+  a:=0 ;
+  b:=1 ;
+  while m <= 10 do
+    begin
+      k:=b ;
+      writeln('F(', m, ') = ', k) ;
+      t:=a+b ;
+      a:=b ;
+      b:=t ;
+      m:=m+1
+    end ;
+  big:=k > 50 ;
+  write('it''s ', big) ;
+  writeln(', sum = ', a+b, '')
+end
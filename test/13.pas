@@ -0,0 +1,23 @@
+begin
+  const N = 5 ;
+    LIMIT = N * 2 ;
+    LOW = -N ;
+    DEBUG = LIMIT > 8 ;
+  array[1..N] of integer a ;
+  array[LOW..LOW+1, 0..N-1] of integer m ;
+  integer i ;
+  integer function scale(x) ;
+    begin
+      const FACTOR = LIMIT div N ;
+      scale:=x*FACTOR
+    end ;
+  i:=1 ;
+  while i <= N do
+    begin
+      a[i]:=scale(i) ;
+      i:=i+1
+    end ;
+  m[LOW, N-1]:=LIMIT ;
+  writeln('a[N] = ', a[N]) ;
+  writeln('m = ', m[-5, 4], ', debug = ', DEBUG)
+end
@@ -0,0 +1,50 @@
+begin
+  const MAX = 100 ;
+  type Score = 0..MAX ;
+    Point = record integer x, y end ;
+    Student = record
+      integer id ;
+      Score score ;
+      Point home
+    end ;
+    Class = array[1..3] of Student ;
+  Class c ;
+  Point origin ;
+  Score s ;
+  integer i ;
+  integer function dist(p, q) ;
+    begin
+      Point p, q ;
+      integer dx, dy ;
+      dx:=p.x-q.x ;
+      dy:=p.y-q.y ;
+      if dx < 0 then dx:=-dx ;
+      if dy < 0 then dy:=-dy ;
+      dist:=dx+dy
+    end ;
+  procedure move(var p, d) ;
+    begin
+      Point p ;
+      p.x:=p.x+d ;
+      p.y:=p.y+d
+    end ;
+  origin.x:=0 ;
+  origin.y:=0 ;
+  i:=1 ;
+  while i <= 3 do
+    begin
+      c[i].id:=i ;
+      c[i].score:=i*30 ;
+      c[i].home.x:=i ;
+      c[i].home.y:=2*i ;
+      i:=i+1
+    end ;
+  move(c[2].home, 5) ;
+  writeln('student ', c[2].id, ' at ', c[2].home.x, ',', c[2].home.y) ;
+  writeln('distance ', dist(c[2].home, origin)) ;
+  origin:=c[3].home ;
+  writeln('origin ', origin.x, ',', origin.y) ;
+  read(s) ;
+  c[1].score:=s+c[3].score ;
+  writeln(c[1].score)
+end
@@ -0,0 +1,29 @@
+begin
+  const N = 5 ;
+  type Digit = 0..9 ;
+  array[1..N] of integer a ;
+  integer i, j, s, n ;
+  Digit d ;
+  for i:=1 to N do a[i]:=i*i ;
+  s:=0 ;
+  for i:=N downto 1 do
+    begin
+      s:=s+a[i] ;
+      write(a[i], ' ')
+    end ;
+  writeln ;
+  writeln('sum = ', s, ', i after loop = ', i) ;
+  n:=3 ;
+  for i:=1 to n do
+    begin
+      n:=10 ;
+      for j:=i to 3 do write('*') ;
+      writeln
+    end ;
+  i:=42 ;
+  for i:=5 to 1 do writeln('never') ;
+  writeln('empty loop leaves i = ', i) ;
+  for d:=7 to 9 do write(d) ;
+  writeln ;
+  for d:=0 downto 0 do writeln('d = ', d)
+end
@@ -0,0 +1,28 @@
+begin
+  const TEN = 10 ;
+  integer i, n ;
+  boolean b ;
+  for i:=0 to 8 do
+    case i of
+      1, 3: write('odd ') ;
+      2, 4: write('even ') ;
+      5..7: write('big ')
+    else
+      write('other ')
+    end ;
+  writeln ;
+  for i:=1 to 3 do
+    begin
+      n:=i * 100 ;
+      case n of
+        100: writeln('hundred') ;
+        200..299: writeln('two hundreds') ;
+        -1, TEN * TEN * TEN: writeln('thousand')
+      end
+    end ;
+  b:=i > 2 ;
+  case b of
+    true: writeln('true') ;
+    false: writeln('false') ;
+  end
+end
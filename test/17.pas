@@ -0,0 +1,32 @@
+begin
+  integer n ;
+  integer function outer(k) ;
+    begin
+      integer k ;
+      integer s ;
+      integer function inner(m) ;
+        begin
+          integer m ;
+          procedure bump ;
+            begin
+              s:=s+1 ;
+              n:=n+1
+            end ;
+          bump ;
+          if m <= 0 then inner:=0
+          else inner:=k+m+inner(m-1)
+        end ;
+      procedure twice(var v) ;
+        begin
+          integer v ;
+          v:=v*2 ;
+          if v < 10 then outer:=v
+        end ;
+      s:=0 ;
+      outer:=inner(k) + s ;
+      twice(k)
+    end ;
+  n:=0 ;
+  writeln(outer(3), ' ', n) ;
+  writeln(outer(9), ' ', n)
+end
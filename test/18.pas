@@ -0,0 +1,33 @@
+begin
+  integer i ;
+  boolean function isEven(n) ; forward ;
+  boolean function isOdd(n) ;
+    begin
+      integer n ;
+      if n = 0 then isOdd:=false
+      else isOdd:=isEven(n-1)
+    end ;
+  boolean function isEven(n) ;
+    begin
+      integer n ;
+      if n = 0 then isEven:=true
+      else isEven:=isOdd(n-1)
+    end ;
+  procedure pong(k, var c) ; forward ;
+  procedure ping(k, var c) ;
+    begin
+      integer k, c ;
+      c:=c+1 ;
+      if k > 0 then pong(k-1, c)
+    end ;
+  procedure pong(k, var c) ;
+    begin
+      integer k, c ;
+      c:=c+10 ;
+      if k > 0 then ping(k-1, c)
+    end ;
+  for i:=0 to 5 do writeln(i, ' even: ', isEven(i), ', odd: ', isOdd(i)) ;
+  i:=0 ;
+  ping(4, i) ;
+  writeln('ping-pong: ', i)
+end
@@ -0,0 +1,8 @@
+uses Shapes, Arith ;
+begin
+  Point p ;
+  integer i ;
+  p.x:=3 ; p.y:=4 ;
+  show(dist2(p.x - ORIGIN, p.y)) ;
+  for i:=1 to 4 do writeln(i, ' squared is ', square(i), ', even: ', even(i))
+end
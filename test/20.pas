@@ -0,0 +1,19 @@
+begin
+  {$DEFINE VERBOSE}
+  integer i, s ;
+  {$I 'Square.inc'}
+  s:=0 ;
+  for i:=1 to LIMIT do
+    begin
+      s:=s+square(i) ;
+      {$IFDEF VERBOSE}
+      writeln(i, ' squared is ', square(i)) ;
+      {$ENDIF}
+      {$IFNDEF DEBUG}
+      if s > 100 then writeln('overflow')
+      {$ELSE}
+      writeln('sum so far: ', s)
+      {$ENDIF}
+    end ;
+  writeln('sum: ', s)
+end
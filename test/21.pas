@@ -0,0 +1,8 @@
+begin
+	// ѧWindowsGBK棬βCRLFƱ
+	integer i, s ;
+	s:=0 ;
+	for i:=1 to 10 do
+		s:=s+i ;
+	writeln('ϼƣ', s)
+end
@@ -0,0 +1,13 @@
+Begin
+  Const LIMIT = 3 ;
+  Integer Total, i ;
+  Integer Function Twice(N) ;
+    BEGIN
+      INTEGER n ;
+      twice:=n*2
+    END ;
+  total:=0 ;
+  For I:=1 To Limit Do total:=TOTAL+twice(i) ;
+  WriteLn('total: ', Total) ;
+  If Total > 10 THEN writeln('big') Else WRITELN('small')
+End
@@ -0,0 +1,14 @@
+begin
+  const MASK = $FF ; HALF = maxint div 2 ;
+  integer x, n ;
+  x:=$1F + MASK ;
+  writeln('x = ', x, ', maxint = ', maxint) ;
+  n:=0 ;
+  while x <= HALF do
+    begin
+      x:=x*2 ;
+      n:=n+1
+    end ;
+  writeln(n, ' doublings before overflow') ;
+  x:=123456789012345678901234567890
+end
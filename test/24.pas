@@ -0,0 +1,12 @@
+begin
+  integer total, k ;
+  procedure show(n) ;
+    begin
+      integer n ;
+      writeln(n)
+    end ;
+  totl:=0 ;
+  for k:=1 to maxnt do total:=total+k ;
+  shwo(total) ;
+  wirte(kk)
+end
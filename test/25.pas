@@ -0,0 +1,3 @@
+begin
+  integer k ;
+  k:=12a
\ No newline at end of file
@@ -0,0 +1,27 @@
+begin
+  integer r ;
+  integer function F(n) ;
+    begin
+      integer n ;
+      F:=n+1
+    end ;
+  integer function G(n) ;
+    begin
+      integer n ;
+      F:=99 ;
+      G:=F
+    end ;
+  integer function H(n) ;
+    begin
+      integer n ;
+      procedure set ;
+        begin
+          H:=n*2
+        end ;
+      set
+    end ;
+  r:=G(1) ;
+  r:=H(2) ;
+  F:=0 ;
+  writeln(r)
+end
@@ -0,0 +1,18 @@
+begin
+  integer n ;
+  n:=maxint ;
+  case n of
+    0..maxint: writeln('non-negative') ;
+    -maxint-1..-1: writeln('negative')
+  end ;
+  n:=-maxint-1 ;
+  case n of
+    -maxint-1, -maxint, -maxint+1, -maxint+2: writeln('bottom') ;
+    maxint-3..maxint: writeln('top')
+  end ;
+  n:=maxint-1 ;
+  case n of
+    maxint-3..maxint: writeln('top')
+  else writeln('other')
+  end
+end
@@ -0,0 +1,18 @@
+begin
+  type Big = array[0..maxint] of integer ;
+  type Pair = record integer x ; Big b end ;
+  type Huge = array[1..3000000000] of integer ;
+  type Half = array[1..1500000000] of integer ;
+  type Row = array[-maxint-1..maxint] of boolean ;
+  Half h ;
+  Half g ;
+  integer function f(a, b) ;
+    begin
+      Half a ;
+      Half b ;
+      f:=1
+    end ;
+  integer k ;
+  k:=f(h, h) ;
+  writeln(k)
+end
@@ -0,0 +1,34 @@
+begin
+  type Row = array[1..3] of integer ;
+  Row r ;
+  integer i ;
+  boolean function isOdd(x) ; forward ;
+  procedure fill(var a, odd) ; forward ;
+  procedure reset(var a) ;
+    begin
+      Row a ;
+      fill(a, true)
+    end ;
+  boolean function isEven(x) ;
+    begin
+      integer x ;
+      isEven:=not isOdd(x)
+    end ;
+  boolean function isOdd(x) ;
+    begin
+      integer x ;
+      isOdd:=x mod 2 = 1
+    end ;
+  procedure fill(var a, odd) ;
+    begin
+      Row a ;
+      boolean odd ;
+      integer i ;
+      for i:=1 to 3 do
+        if odd then a[i]:=2*i-1 else a[i]:=2*i
+    end ;
+  reset(r) ;
+  for i:=1 to 3 do writeln(r[i], ' even: ', isEven(r[i])) ;
+  fill(r, isOdd(2)) ;
+  for i:=1 to 3 do writeln(r[i], ' even: ', isEven(r[i]))
+end
@@ -0,0 +1,21 @@
+begin
+  integer i, k ;
+  procedure q ;
+    begin
+      i:=0
+    end ;
+  procedure r ;
+    begin
+      read(i)
+    end ;
+  procedure s(n) ;
+    begin
+      integer n, j ;
+      for n:=1 to 3 do writeln(n) ;
+      for k:=1 to 3 do writeln(k) ;
+      for j:=1 to 3 do writeln(j)
+    end ;
+  for i:=1 to 3 do q ;
+  k:=i ;
+  writeln(k)
+end
@@ -0,0 +1,8 @@
+uses Shapes ;
+begin
+  Point p ;
+  p.x:=6 ; p.y:=8 ;
+  show(norm2(p)) ;
+  mark(norm2(p) > 50, p.x) ;
+  mark(false, p.y)
+end
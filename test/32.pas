@@ -0,0 +1,7 @@
+begin
+  integer k ; {$IFDEF X}
+  integer j ; {$ENDIF} integer m ;
+  k:=1 ; {$IFDEF X} k:=2 ; {$ENDIF}
+  {$IFNDEF X} m:=3 ; {$ELSE} m:=4 ; {$ENDIF} k:=k+m ;
+  writeln(k)
+end
@@ -0,0 +1,24 @@
+begin
+  const N = 3 ;
+  type Small = 1..10 ;
+  integer r ;
+  integer function f(a) ;
+    begin
+      type T = 1..10 ;
+      T a ;
+      f:=a
+    end ;
+  integer function g(v) ;
+    begin
+      const M = 4 ;
+      array[1..M] of integer v ;
+      g:=v[1]
+    end ;
+  integer function h(a, v) ;
+    begin
+      Small a ;
+      array[1..N] of integer v ;
+      h:=a+v[N]
+    end ;
+  r:=f(2)
+end
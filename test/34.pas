@@ -0,0 +1,6 @@
+begin
+  integer i ;
+  {$I 'inc/Loop.inc'}
+  i:=1 ;
+  writeln(i)
+end
@@ -0,0 +1,6 @@
+begin
+  integer count ;
+  count:=0 ;
+  whlie count < 3 do count:=count+1 ;
+  write(count)
+end
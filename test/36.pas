@@ -0,0 +1,6 @@
+begin
+  integer i, s ;
+  s:=0 ;
+  fro i := 1 to 3 do s:=s+i ;
+  write(s)
+end
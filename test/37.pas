@@ -0,0 +1,8 @@
+begin
+  integer n ;
+  n:=0 ;
+  repaet
+    n:=n+1
+  until n >= 3 ;
+  write(n)
+end
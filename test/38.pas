@@ -0,0 +1,18 @@
+begin
+  integer n, k ;
+  array[1..5] of integer a ;
+  integer function F(n) ;
+    begin
+      integer n ;
+      if n<=0
+      then F:=1
+      else F:=n*F(n-1)
+    end ;
+  read(n) ;
+  for k:=1 to 5 do a[k]:=F(k) ;
+  for k:=1 to 5 do writeln(a[k]) ;
+  writeln(F(n)) ;
+  read(n) ;
+  writeln(n div 3) ;
+  writeln(n mod 3)
+end
@@ -0,0 +1,8 @@
+begin
+  integer a, b ;
+  read(a) ;
+  read(b) ;
+  writeln(a div b) ;
+  b:=b-2 ;
+  writeln(a div b)
+end
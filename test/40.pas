@@ -0,0 +1,10 @@
+begin
+  integer k ;
+  array[1..3] of integer a ;
+  for k:=1 to 3 do a[k]:=k*k ;
+  k:=0 ;
+  repeat
+    k:=k+1 ;
+    writeln(a[k])
+  until k > 3
+end
@@ -0,0 +1,10 @@
+begin
+  integer n, k ;
+  n:=1 ;
+  k:=0 ;
+  repeat
+    k:=k+1 ;
+    writeln(n) ;
+    n:=n*1000000
+  until k > 4
+end
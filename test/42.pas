@@ -0,0 +1,9 @@
+begin
+  integer k ;
+  array[1..3] of integer a ;
+  for k:=1 to 3 do a[k]:=k ;
+  writeln(a[3]) ;
+  read(k) ;
+  a[k]:=1 ;
+  writeln(a[k])
+end
@@ -0,0 +1,10 @@
+begin
+  integer n ;
+  writeln(maxint) ;
+  n:=1 ;
+  repeat
+    n:=n*2 ;
+    writeln(n)
+  until n > maxint div 2 ;
+  n:=n*2
+end
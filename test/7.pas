@@ -0,0 +1,16 @@
+begin
+  integer k ;
+  integer m ;
+  integer s ;
+  read(m) ;
+  k:=m ;
+  s:=0 ;
+  while k>0 do
+    k:=k-1 ;
+  repeat
+    s:=s-k ;
+    k:=k-1 ;
+    writeln(s) ;
+  until k<=0-m ;
+  writeln(s) ;
+end
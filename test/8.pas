@@ -0,0 +1,20 @@
+begin
+  integer a ;
+  integer b ;
+  read(a) ;
+  read(b) ;
+  if a>b then
+    begin
+      a:=a-b ;
+      b:=b*2
+    end ;
+  if a>0 then
+    if b>0 then writeln(a)
+    else writeln(b) ;
+  while a>0 do
+    begin
+      a:=a-1 ;
+      ;
+    end ;
+  writeln(a) ;
+end
@@ -0,0 +1,21 @@
+begin
+  integer x, y, g ;
+  integer function gcd(a, b) ;
+    begin
+      integer a, b ;
+      if b = 0 then gcd:=a
+      else gcd:=gcd(b, a mod b)
+    end ;
+  boolean function between(v, lo, hi) ;
+    begin
+      integer v, lo, hi ;
+      between:=(lo <= v) and (v <= hi)
+    end ;
+  boolean ok ;
+  read(x) ;
+  read(y) ;
+  g:=gcd(x, y) ;
+  writeln(g) ;
+  ok:=between(g, 1, 10) ;
+  writeln(ok) ;
+end
@@ -0,0 +1,21 @@
+unit Arith ;
+interface
+  integer function square(n) ;
+  boolean function even(n) ;
+implementation
+  integer function helper(n) ;
+    begin
+      integer n ;
+      helper:=n * n
+    end ;
+  integer function square(n) ;
+    begin
+      integer n ;
+      square:=helper(n)
+    end ;
+  boolean function even(n) ;
+    begin
+      integer n ;
+      even:=n mod 2 = 0
+    end ;
+end
@@ -0,0 +1,32 @@
+unit Shapes ;
+uses Arith ;
+interface
+  type Point = record integer x ; integer y end ;
+  const ORIGIN = 0 ;
+  integer function dist2(dx, dy) ;
+  procedure show(v) ;
+  integer function norm2(var p) ;
+  procedure mark(b, v) ;
+implementation
+  integer function dist2(dx, dy) ;
+    begin
+      integer dx, dy ;
+      dist2:=square(dx) + square(dy)
+    end ;
+  procedure show(v) ;
+    begin
+      integer v ;
+      writeln('value: ', v)
+    end ;
+  integer function norm2(var p) ;
+    begin
+      Point p ;
+      norm2:=dist2(p.x, p.y)
+    end ;
+  procedure mark(b, v) ;
+    begin
+      boolean b ;
+      integer v ;
+      if b then writeln('marked: ', v)
+    end ;
+end